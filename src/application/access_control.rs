@@ -0,0 +1,186 @@
+use ::config::{Config, File};
+use glob::Pattern;
+use serde::{Deserialize, Serialize};
+
+use crate::application::manage_service::ServiceAction;
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    /// May look at services and logs only.
+    Viewer,
+    /// May start, stop, restart and signal services.
+    Operator,
+    /// May do anything, including enabling and disabling services.
+    Admin,
+}
+
+impl Role {
+    fn allows(&self, action: &ServiceAction) -> bool {
+        match action {
+            ServiceAction::Check => true,
+            // These take the service out of supervision or change what runs at boot
+            ServiceAction::Enable
+            | ServiceAction::Disable
+            | ServiceAction::Exit
+            | ServiceAction::ForceShutdown => *self >= Role::Admin,
+            _ => *self >= Role::Operator,
+        }
+    }
+}
+
+fn all_services() -> Vec<String> {
+    vec!["*".to_string()]
+}
+
+fn default_role() -> Role {
+    Role::Viewer
+}
+
+#[derive(Deserialize, Debug)]
+struct GrantFile {
+    users: Vec<String>,
+    role: Role,
+    #[serde(default = "all_services")]
+    services: Vec<String>,
+}
+
+#[derive(Deserialize, Debug)]
+struct AccessPolicyFile {
+    #[serde(default = "default_role")]
+    default_role: Role,
+    #[serde(default)]
+    protected_services: Vec<String>,
+    #[serde(default)]
+    grants: Vec<GrantFile>,
+}
+
+#[derive(Debug, Clone)]
+struct Grant {
+    users: Vec<String>,
+    role: Role,
+    services: Vec<Pattern>,
+}
+
+/// Maps users to roles per service, loaded from the `--acl-file`.
+///
+/// A user's role for a service is the highest role among the grants naming
+/// the user and matching the service, or `default_role` if none do.
+/// Protected services can only be managed by admins.
+#[derive(Debug, Clone)]
+pub struct AccessPolicy {
+    default_role: Role,
+    protected_services: Vec<Pattern>,
+    grants: Vec<Grant>,
+}
+
+fn compile_patterns(patterns: &[String]) -> Result<Vec<Pattern>, String> {
+    patterns
+        .iter()
+        .map(|pattern| Pattern::new(pattern).map_err(|e| format!("Invalid service pattern {}: {}", pattern, e)))
+        .collect()
+}
+
+impl AccessPolicy {
+    /// Policy used without an ACL file: every authenticated user is an admin.
+    pub fn unrestricted() -> Self {
+        Self {
+            default_role: Role::Admin,
+            protected_services: Vec::new(),
+            grants: Vec::new(),
+        }
+    }
+
+    /// Loads a policy file in any format supported by the `config` crate (TOML, YAML, JSON).
+    /// `own_service`, the service running this UI, is always protected, so only admins can
+    /// take the UI down.
+    pub fn load(path: &str, own_service: &str) -> Result<Self, String> {
+        let policy: AccessPolicyFile = Config::builder()
+            .add_source(File::with_name(path))
+            .build()
+            .and_then(|settings| settings.try_deserialize())
+            .map_err(|e| format!("Failed to load ACL file {}: {}", path, e))?;
+
+        let grants = policy
+            .grants
+            .into_iter()
+            .map(|grant| {
+                Ok(Grant {
+                    users: grant.users,
+                    role: grant.role,
+                    services: compile_patterns(&grant.services)?,
+                })
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+
+        let mut protected_services = compile_patterns(&policy.protected_services)?;
+        protected_services.push(Pattern::new(&Pattern::escape(own_service)).map_err(|e| e.to_string())?);
+
+        Ok(Self {
+            default_role: policy.default_role,
+            protected_services,
+            grants,
+        })
+    }
+
+    /// Effective role of `user` (`None` when authentication is disabled) for `service`.
+    pub fn role_for(&self, user: Option<&str>, service: &str) -> Role {
+        let role = user
+            .map(|user| {
+                self.grants
+                    .iter()
+                    .filter(|grant| grant.users.iter().any(|u| u == user))
+                    .filter(|grant| grant.services.iter().any(|pattern| pattern.matches(service)))
+                    .map(|grant| grant.role)
+                    .fold(self.default_role, Role::max)
+            })
+            .unwrap_or(self.default_role);
+
+        if role < Role::Admin && self.protected_services.iter().any(|pattern| pattern.matches(service)) {
+            Role::Viewer
+        } else {
+            role
+        }
+    }
+
+    pub fn allows(&self, user: Option<&str>, service: &str, action: &ServiceAction) -> bool {
+        self.role_for(user, service).allows(action)
+    }
+
+    /// The log config can name a processor that svlogd runs, so editing it is reserved to admins.
+    pub fn may_edit_log_config(&self, user: Option<&str>, service: &str) -> bool {
+        self.role_for(user, service) >= Role::Admin
+    }
+
+    /// Names of the actions `user` may perform on `service`, for the UI.
+    pub fn allowed_actions(&self, user: Option<&str>, service: &str) -> Vec<&'static str> {
+        let role = self.role_for(user, service);
+        ServiceAction::ALL
+            .iter()
+            .filter(|action| role.allows(action))
+            .map(|action| action.as_str())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn own_service_is_protected_from_broad_grants() {
+        let path = std::env::temp_dir().join(format!("runit-ui-acl-{}.toml", std::process::id()));
+        std::fs::write(
+            &path,
+            "[[grants]]\nusers = [\"bob\"]\nrole = \"operator\"\nservices = [\"*\"]\n\n[[grants]]\nusers = [\"alice\"]\nrole = \"admin\"\n",
+        )
+        .unwrap();
+        let policy = AccessPolicy::load(path.to_str().unwrap(), "runit-ui");
+        std::fs::remove_file(&path).unwrap();
+        let policy = policy.unwrap();
+
+        assert!(policy.allows(Some("bob"), "nginx", &ServiceAction::Stop));
+        assert!(!policy.allows(Some("bob"), "runit-ui", &ServiceAction::Stop));
+        assert!(policy.allows(Some("alice"), "runit-ui", &ServiceAction::Stop));
+    }
+}
@@ -0,0 +1,170 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+use log::error;
+use rev_lines::RevLines;
+use serde::{Deserialize, Serialize};
+
+use crate::application::manage_service::ActionOutput;
+use crate::error::AppError;
+
+/// Number of entries returned by `/api/audit` when no limit is given.
+const DEFAULT_QUERY_LIMIT: usize = 100;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Outcome {
+    Success,
+    Denied,
+    Failure,
+}
+
+/// One recorded service action.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AuditEntry {
+    /// Seconds since the UNIX epoch.
+    pub timestamp: u64,
+    pub user: Option<String>,
+    pub client_ip: Option<String>,
+    pub service: String,
+    pub action: String,
+    pub outcome: Outcome,
+    pub message: String,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+impl AuditEntry {
+    pub fn new(
+        user: Option<String>,
+        client_ip: Option<String>,
+        service: &str,
+        action: &str,
+        result: &Result<ActionOutput, AppError>,
+    ) -> Self {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+
+        let (outcome, message, stdout, stderr) = match result {
+            Ok(output) => (Outcome::Success, output.message.clone(), output.stdout.clone(), output.stderr.clone()),
+            // Only the access policy denies; the system refusing access is a failure to perform it
+            Err(e @ AppError::Forbidden(_)) => (Outcome::Denied, e.to_string(), String::new(), String::new()),
+            Err(AppError::SvFailure { message, stdout, stderr, .. }) => {
+                (Outcome::Failure, message.clone(), stdout.clone(), stderr.clone())
+            }
+            Err(e) => (Outcome::Failure, e.to_string(), String::new(), String::new()),
+        };
+
+        Self {
+            timestamp,
+            user,
+            client_ip,
+            service: service.to_string(),
+            action: action.to_string(),
+            outcome,
+            message,
+            stdout,
+            stderr,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Default)]
+pub struct AuditFilter {
+    pub service: Option<String>,
+    pub user: Option<String>,
+    /// Only entries at or after this UNIX timestamp.
+    pub since: Option<u64>,
+    /// Only entries at or before this UNIX timestamp.
+    pub until: Option<u64>,
+    pub limit: Option<usize>,
+}
+
+impl AuditFilter {
+    fn matches(&self, entry: &AuditEntry) -> bool {
+        self.service.as_ref().is_none_or(|service| &entry.service == service)
+            && self.user.as_ref().is_none_or(|user| entry.user.as_ref() == Some(user))
+            && self.since.is_none_or(|since| entry.timestamp >= since)
+            && self.until.is_none_or(|until| entry.timestamp <= until)
+    }
+}
+
+/// Append-only JSON lines file of every service action.
+pub struct AuditLog {
+    path: PathBuf,
+    writer: Mutex<File>,
+}
+
+impl AuditLog {
+    pub fn open(path: &str) -> io::Result<Self> {
+        let path = PathBuf::from(path);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+
+        Ok(Self {
+            path,
+            writer: Mutex::new(file),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn record(&self, entry: &AuditEntry) -> io::Result<()> {
+        let mut line = serde_json::to_vec(entry)?;
+        line.push(b'\n');
+
+        let mut writer = self.writer.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        writer.write_all(&line)?;
+        writer.sync_data()
+    }
+
+    /// Records `entry` on the blocking pool, so a slow disk doesn't stall the async workers.
+    /// Failures are only logged, since the action itself already happened.
+    pub async fn record_async(self: Arc<Self>, entry: AuditEntry) {
+        let (service, action) = (entry.service.clone(), entry.action.clone());
+        let result = tokio::task::spawn_blocking(move || self.record(&entry))
+            .await
+            .unwrap_or_else(|e| Err(io::Error::other(e)));
+        if let Err(e) = result {
+            error!("Failed to record audit entry for {} {}: {}", action, service, e);
+        }
+    }
+
+    /// Entries matching `filter`, newest first.
+    pub fn query(&self, filter: &AuditFilter) -> io::Result<Vec<AuditEntry>> {
+        let reader = BufReader::new(File::open(&self.path)?);
+        let limit = filter.limit.unwrap_or(DEFAULT_QUERY_LIMIT);
+
+        Ok(RevLines::new(reader)
+            .filter_map(Result::ok)
+            .filter_map(|line| serde_json::from_str::<AuditEntry>(&line).ok())
+            .filter(|entry| filter.matches(entry))
+            .take(limit)
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn outcome(result: Result<ActionOutput, AppError>) -> Outcome {
+        AuditEntry::new(None, None, "nginx", "stop", &result).outcome
+    }
+
+    #[test]
+    fn only_policy_rejections_are_denied() {
+        assert_eq!(outcome(Err(AppError::Forbidden("bob may not stop nginx".to_string()))), Outcome::Denied);
+        let eacces = io::Error::from(io::ErrorKind::PermissionDenied);
+        assert_eq!(outcome(Err(AppError::io("Failed to run sv", eacces))), Outcome::Failure);
+        assert_eq!(outcome(Err(AppError::Timeout("sv down nginx".to_string()))), Outcome::Failure);
+    }
+}
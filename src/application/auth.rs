@@ -0,0 +1,130 @@
+use std::collections::HashMap;
+use std::fs;
+use argon2::{Argon2, PasswordVerifier};
+use log::warn;
+use subtle::ConstantTimeEq;
+
+/// Name of the user who passed basic authentication, stored in the request extensions.
+#[derive(Debug, Clone)]
+pub struct AuthenticatedUser(pub String);
+
+/// A password as stored in the credential source.
+#[derive(Debug, Clone)]
+enum StoredPassword {
+    /// Given on the command line; compared in constant time.
+    Plain(String),
+    /// `$2a$`, `$2b$` or `$2y$` bcrypt hash.
+    Bcrypt(String),
+    /// PHC formatted `$argon2id$` / `$argon2i$` / `$argon2d$` hash.
+    Argon2(String),
+}
+
+impl StoredPassword {
+    fn parse_hash(hash: &str) -> Option<Self> {
+        if hash.starts_with("$2a$") || hash.starts_with("$2b$") || hash.starts_with("$2y$") {
+            Some(StoredPassword::Bcrypt(hash.to_string()))
+        } else if hash.starts_with("$argon2") {
+            Some(StoredPassword::Argon2(hash.to_string()))
+        } else {
+            None
+        }
+    }
+
+    fn verify(&self, password: &str) -> bool {
+        match self {
+            StoredPassword::Plain(expected) => expected.as_bytes().ct_eq(password.as_bytes()).into(),
+            StoredPassword::Bcrypt(hash) => bcrypt::verify(password, hash).unwrap_or_else(|e| {
+                warn!("Failed to verify bcrypt hash: {}", e);
+                false
+            }),
+            StoredPassword::Argon2(hash) => Argon2::default()
+                .verify_password(password.as_bytes(), hash.as_str())
+                .is_ok(),
+        }
+    }
+}
+
+/// Credential store consulted by the basic authentication middleware.
+#[derive(Debug, Clone)]
+pub struct Authenticator {
+    /// `None` when authentication was explicitly turned off with `--no-auth`.
+    users: Option<HashMap<String, StoredPassword>>,
+}
+
+impl Authenticator {
+    pub fn disabled() -> Self {
+        Self { users: None }
+    }
+
+    /// Single user with a plaintext password from the command line.
+    pub fn from_plaintext(username: &str, password: &str) -> Self {
+        let mut users = HashMap::new();
+        users.insert(username.to_string(), StoredPassword::Plain(password.to_string()));
+        Self { users: Some(users) }
+    }
+
+    /// Loads an htpasswd-style file of `user:hash` lines with bcrypt or argon2 hashes.
+    pub fn from_htpasswd(path: &str) -> Result<Self, String> {
+        let content = fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read htpasswd file {}: {}", path, e))?;
+
+        let mut users = HashMap::new();
+        for (number, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (username, hash) = line
+                .split_once(':')
+                .ok_or_else(|| format!("{}:{}: expected user:hash", path, number + 1))?;
+            let password = StoredPassword::parse_hash(hash)
+                .ok_or_else(|| format!("{}:{}: unsupported hash for user {}, use bcrypt or argon2", path, number + 1, username))?;
+            users.insert(username.to_string(), password);
+        }
+
+        if users.is_empty() {
+            return Err(format!("No users defined in htpasswd file {}", path));
+        }
+
+        Ok(Self { users: Some(users) })
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.users.is_some()
+    }
+
+    pub fn verify(&self, username: &str, password: &str) -> bool {
+        match &self.users {
+            None => true,
+            Some(users) => match users.get(username) {
+                Some(stored) => stored.verify(password),
+                None => {
+                    // Hash anyway, against a stored hash of the same cost, so response times
+                    // don't tell which usernames exist; a match doesn't count for anyone.
+                    if let Some(dummy) = users.values().next() {
+                        let _ = dummy.verify(password);
+                    }
+                    false
+                }
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unknown_user_is_rejected_even_with_a_stored_password() {
+        let hash = bcrypt::hash("secret", 4).unwrap();
+        let mut users = HashMap::new();
+        users.insert("admin".to_string(), StoredPassword::parse_hash(&hash).unwrap());
+        let authenticator = Authenticator { users: Some(users) };
+
+        assert!(authenticator.verify("admin", "secret"));
+        assert!(!authenticator.verify("admin", "wrong"));
+        assert!(!authenticator.verify("nobody", "secret"));
+    }
+}
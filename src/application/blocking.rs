@@ -0,0 +1,18 @@
+use std::time::Duration;
+
+use crate::error::AppError;
+
+/// Runs blocking work such as file and `/proc` reads on tokio's blocking pool, so it can't
+/// stall the actix workers. After `timeout` the caller gets a timeout error; the work itself
+/// can't be interrupted and finishes in the background.
+pub async fn run_blocking<T, F>(operation: &str, timeout: Duration, work: F) -> Result<T, AppError>
+where
+    T: Send + 'static,
+    F: FnOnce() -> Result<T, AppError> + Send + 'static,
+{
+    match tokio::time::timeout(timeout, tokio::task::spawn_blocking(work)).await {
+        Ok(Ok(result)) => result,
+        Ok(Err(e)) => Err(AppError::io(format!("{} failed", operation), std::io::Error::other(e))),
+        Err(_) => Err(AppError::Timeout(format!("{} took longer than {}s", operation, timeout.as_secs()))),
+    }
+}
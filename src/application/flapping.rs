@@ -0,0 +1,175 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use crate::application::service_info::ServiceInfo;
+use crate::config::app_config::AppConfig;
+use crate::domain::supervise_status::Want;
+
+/// When a service counts as flapping, and whether it is stopped for it.
+#[derive(Debug, Clone, Copy)]
+pub struct FlapPolicy {
+    /// Restarts older than this are forgotten.
+    pub window: Duration,
+    /// Restarts within the window from which the service is flapping.
+    pub threshold: usize,
+    /// Restarts within the window after which the service is taken down with `sv down`.
+    pub stop_after: Option<usize>,
+}
+
+impl FlapPolicy {
+    pub fn from_config(config: &AppConfig) -> Self {
+        Self {
+            window: Duration::from_secs(config.flap_window),
+            threshold: config.flap_threshold,
+            stop_after: config.flap_stop_after,
+        }
+    }
+}
+
+/// Restarts of one service, seen as changes of its pid between reads.
+///
+/// Several restarts between two reads show up as a single pid change. With inotify every
+/// restart triggers a read, but where the cache falls back to polling every `stale_after`
+/// seconds, a service restarting faster than that is undercounted and may never reach the
+/// threshold; keep `flap_window` several times `stale_after` there.
+#[derive(Debug, Clone, Default)]
+pub struct RestartHistory {
+    last_pid: Option<u32>,
+    restarts: VecDeque<Instant>,
+    /// An `sv down` was sent and hasn't taken effect yet.
+    stopping: bool,
+    /// The `sv down` took effect; kept until the service is wanted up again.
+    stopped_for_flapping: bool,
+}
+
+impl RestartHistory {
+    /// Records a fresh read of the service. Being taken down on purpose, by an admin or
+    /// by `stop_after`, starts over, so the service can be started again. Only wanting it up,
+    /// through `sv up`, `start` or `restart`, clears that it was stopped for flapping.
+    pub fn record(&mut self, info: &ServiceInfo, policy: &FlapPolicy) {
+        match info.flags.want {
+            Some(Want::Down) => {
+                let stopped_for_flapping = self.stopped_for_flapping || self.stopping;
+                *self = Self { stopped_for_flapping, ..Self::default() };
+                return;
+            }
+            Some(Want::Up) => self.stopped_for_flapping = false,
+            None => {}
+        }
+
+        if let Some(pid) = info.pid {
+            if self.last_pid.is_some_and(|last_pid| last_pid != pid) {
+                self.restarts.push_back(Instant::now());
+            }
+            self.last_pid = Some(pid);
+        }
+        while self.restarts.front().is_some_and(|restart| restart.elapsed() > policy.window) {
+            self.restarts.pop_front();
+        }
+        // Once the restarts aged out, a failed `sv down` may be sent again
+        if self.restarts.is_empty() {
+            self.stopping = false;
+        }
+    }
+
+    /// Restarts within the window.
+    pub fn restarts(&self, policy: &FlapPolicy) -> usize {
+        self.restarts.iter().filter(|restart| restart.elapsed() <= policy.window).count()
+    }
+
+    /// Sets the restart count, `flapping` and `stopped_for_flapping` of `info` as of now.
+    pub fn annotate(&self, info: &mut ServiceInfo, policy: &FlapPolicy) {
+        info.restarts = self.restarts(policy);
+        info.flapping = info.restarts >= policy.threshold;
+        info.stopped_for_flapping = self.stopped_for_flapping;
+    }
+
+    /// Whether the service should be taken down now; answers yes once per attempt.
+    pub fn take_stop(&mut self, info: &ServiceInfo, policy: &FlapPolicy) -> bool {
+        let due = policy.stop_after.is_some_and(|stop_after| self.restarts(policy) >= stop_after);
+        if due && !self.stopping && info.is_running() {
+            self.stopping = true;
+            return true;
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::application::service_info::StateFlags;
+    use crate::domain::service::Registration;
+
+    fn policy(window: Duration) -> FlapPolicy {
+        FlapPolicy { window, threshold: 2, stop_after: Some(3) }
+    }
+
+    fn read(pid: Option<u32>, want: Option<Want>) -> ServiceInfo {
+        let status = if pid.is_some() { "run" } else { "down" };
+        let flags = StateFlags { want, ..StateFlags::default() };
+        ServiceInfo::new("demo".to_string(), status.to_string(), pid, Some(1), flags, Registration::default(), None)
+    }
+
+    /// Records a read with `pid` and returns it annotated.
+    fn observe(history: &mut RestartHistory, policy: &FlapPolicy, pid: Option<u32>, want: Option<Want>) -> ServiceInfo {
+        let mut info = read(pid, want);
+        history.record(&info, policy);
+        history.annotate(&mut info, policy);
+        info
+    }
+
+    #[test]
+    fn flaps_from_the_threshold_and_stops_once_at_stop_after() {
+        let policy = policy(Duration::from_secs(60));
+        let mut history = RestartHistory::default();
+
+        let info = observe(&mut history, &policy, Some(1), None);
+        assert_eq!((info.restarts, info.flapping), (0, false));
+        let info = observe(&mut history, &policy, Some(1), None);
+        assert_eq!((info.restarts, info.flapping), (0, false));
+        let info = observe(&mut history, &policy, Some(2), None);
+        assert_eq!((info.restarts, info.flapping), (1, false));
+        let info = observe(&mut history, &policy, Some(3), None);
+        assert_eq!((info.restarts, info.flapping), (2, true));
+        assert!(!history.take_stop(&info, &policy));
+
+        let info = observe(&mut history, &policy, Some(4), None);
+        assert!(history.take_stop(&info, &policy));
+        let info = observe(&mut history, &policy, Some(5), None);
+        assert!(!history.take_stop(&info, &policy), "sv down is only sent once per attempt");
+    }
+
+    #[test]
+    fn restarts_age_out_of_the_window() {
+        let policy = policy(Duration::from_millis(20));
+        let mut history = RestartHistory::default();
+        for pid in 1..=3 {
+            observe(&mut history, &policy, Some(pid), None);
+        }
+        assert_eq!(history.restarts(&policy), 2);
+
+        std::thread::sleep(Duration::from_millis(30));
+        let info = observe(&mut history, &policy, Some(3), None);
+        assert_eq!((info.restarts, info.flapping), (0, false));
+    }
+
+    #[test]
+    fn stopped_for_flapping_lasts_until_wanted_up() {
+        let policy = policy(Duration::from_secs(60));
+        let mut history = RestartHistory::default();
+        let mut info = read(None, None);
+        for pid in 1..=4 {
+            info = observe(&mut history, &policy, Some(pid), None);
+        }
+        assert!(history.take_stop(&info, &policy));
+
+        let info = observe(&mut history, &policy, None, Some(Want::Down));
+        assert_eq!((info.restarts, info.stopped_for_flapping), (0, true));
+        let info = observe(&mut history, &policy, None, None);
+        assert!(info.stopped_for_flapping);
+
+        let info = observe(&mut history, &policy, Some(5), Some(Want::Up));
+        assert_eq!((info.restarts, info.stopped_for_flapping), (0, false));
+    }
+}
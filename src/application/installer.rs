@@ -6,6 +6,7 @@ use std::os::unix::fs::PermissionsExt;
 use std::env;
 use tinytemplate::TinyTemplate;
 use std::collections::HashMap;
+use crate::config::layout::RunitLayout;
 use crate::Args;
 use crate::TEMPLATES_DIR;
 
@@ -23,9 +24,10 @@ fn is_binary_in_path(binary_name: &str) -> bool {
 
 /// Installs the service to the host system.
 ///
-/// This function installs the currently running binary to `/usr/local/bin/<service_name>`.
-/// It then creates the service directory structure in `/etc/sv/<service_name>`,
-/// and symlink it to `/etc/service/<service_name>`.
+/// This function installs the currently running binary to `<bin_dir>/<service_name>`.
+/// It then creates the service directory structure in `<services_dir>/<service_name>`,
+/// and symlink it to `<active_dir>/<service_name>`, using the directories of `layout`
+/// (e.g. `/usr/local/bin`, `/etc/sv` and `/etc/service` on Debian).
 ///
 /// Note that this function will not overwrite an existing service directory.
 /// If the service is already installed, this function will return an error.
@@ -34,11 +36,13 @@ fn is_binary_in_path(binary_name: &str) -> bool {
 ///
 /// This function will return an error if the service is already installed,
 /// or if there is an error while creating the service directory structure.
-pub fn install_service(args: &Args) -> Result<(), Box<dyn std::error::Error>> {
+pub fn install_service(args: &Args, layout: &RunitLayout) -> Result<(), Box<dyn std::error::Error>> {
     // Install the binary if it's not in the PATH
     if !is_binary_in_path(&args.service_name) {
         let binary_path = env::current_exe()?;
-        let target_path = format!("/usr/local/bin/{}", &args.service_name);
+        let target_path = format!("{}/{}", layout.bin_dir, &args.service_name);
+        fs::create_dir_all(&layout.bin_dir)
+            .map_err(|e| format!("Failed to create {}: {}", layout.bin_dir, e))?;
 
         if !std::path::Path::new(&target_path).exists() {
             fs::copy(&binary_path, &target_path)
@@ -56,7 +60,7 @@ pub fn install_service(args: &Args) -> Result<(), Box<dyn std::error::Error>> {
             .map_err(|e| format!("Failed to set permissions on {}: {}", args.log_directory, e))?;
     }
 
-    let service_dir = format!("/etc/sv/{}", &args.service_name);
+    let service_dir = layout.service_dir(&args.service_name);
     let log_dir = format!("{}/log", service_dir);
 
     // Create the service and log directories if they do not exist
@@ -84,13 +88,19 @@ pub fn install_service(args: &Args) -> Result<(), Box<dyn std::error::Error>> {
     )?;
 
     let mut context = HashMap::new();
-    context.insert("service_name", args.service_name.clone());
+    context.insert("service_name", args.service_name.to_string());
     context.insert("log_directory", args.log_directory.clone());
-    context.insert("services_dir", args.services_dir.clone());
+    context.insert("services_dir", layout.services_dir.clone());
+    context.insert("active_dir", layout.active_dir.clone());
+    context.insert("bin_dir", layout.bin_dir.clone());
     context.insert("log_level", args.log_level.clone());
     context.insert("bind", args.bind.clone());
     context.insert("username", args.username.clone().unwrap_or_default());
     context.insert("password", args.password.clone().unwrap_or_default());
+    context.insert("htpasswd_file", args.htpasswd_file.clone().unwrap_or_default());
+    context.insert("acl_file", args.acl_file.clone().unwrap_or_default());
+    context.insert("audit_file", args.audit_file.clone());
+    context.insert("no_auth", if args.no_auth { "1".to_string() } else { String::new() });
 
     let rendered_run_script = tt.render("run", &context)?;
     let rendered_log_run_script = tt.render("log_run", &context)?;
@@ -105,8 +115,8 @@ pub fn install_service(args: &Args) -> Result<(), Box<dyn std::error::Error>> {
     fs::set_permissions(format!("{}/run", log_dir), fs::Permissions::from_mode(0o755))
         .map_err(|e| format!("Failed to set permissions on log run script: {}", e))?;
 
-    // Create a symlink in /etc/service if it doesn't exist
-    let symlink_path = format!("/etc/service/{}", &args.service_name);
+    // Create a symlink in the active service directory if it doesn't exist
+    let symlink_path = layout.active_link(&args.service_name);
     if !std::path::Path::new(&symlink_path).exists() {
         symlink(&service_dir, symlink_path)
             .map_err(|e| format!("Failed to create symlink: {}", e))?;
@@ -1,74 +1,248 @@
-use std::os::unix::fs::symlink;
-use std::fs::remove_file;
-use std::process::{Command, Output};
-use std::io;
+use std::process::Output;
+use std::time::Duration;
+use tokio::process::Command;
 
+use crate::application::blocking::run_blocking;
+use crate::application::service_info::ServiceInfo;
+use crate::config::layout::RunitLayout;
+use crate::domain::service_logs;
+use crate::domain::service_name::ServiceName;
+use crate::domain::svlogd_config::SvlogdConfig;
+use crate::error::AppError;
 
-#[derive(Debug)]
-enum ServiceAction {
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ServiceAction {
     Start,
     Stop,
     Restart,
     Enable,
     Disable,
+    Once,
+    Pause,
+    Cont,
+    Hup,
+    Alarm,
+    Interrupt,
+    Quit,
+    Usr1,
+    Usr2,
+    Term,
+    Kill,
+    Exit,
+    Check,
+    ForceStop,
+    ForceRestart,
+    ForceShutdown,
 }
 
 impl ServiceAction {
-    fn from_str(action: &str) -> Option<Self> {
-        match action {
-            "start" => Some(ServiceAction::Start),
-            "stop" => Some(ServiceAction::Stop),
-            "restart" => Some(ServiceAction::Restart),
-            "enable" => Some(ServiceAction::Enable),
-            "disable" => Some(ServiceAction::Disable),
-            _ => None,
+    pub const ALL: [ServiceAction; 21] = [
+        ServiceAction::Start,
+        ServiceAction::Stop,
+        ServiceAction::Restart,
+        ServiceAction::Enable,
+        ServiceAction::Disable,
+        ServiceAction::Once,
+        ServiceAction::Pause,
+        ServiceAction::Cont,
+        ServiceAction::Hup,
+        ServiceAction::Alarm,
+        ServiceAction::Interrupt,
+        ServiceAction::Quit,
+        ServiceAction::Usr1,
+        ServiceAction::Usr2,
+        ServiceAction::Term,
+        ServiceAction::Kill,
+        ServiceAction::Exit,
+        ServiceAction::Check,
+        ServiceAction::ForceStop,
+        ServiceAction::ForceRestart,
+        ServiceAction::ForceShutdown,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ServiceAction::Start => "start",
+            ServiceAction::Stop => "stop",
+            ServiceAction::Restart => "restart",
+            ServiceAction::Enable => "enable",
+            ServiceAction::Disable => "disable",
+            ServiceAction::Once => "once",
+            ServiceAction::Pause => "pause",
+            ServiceAction::Cont => "cont",
+            ServiceAction::Hup => "hup",
+            ServiceAction::Alarm => "alarm",
+            ServiceAction::Interrupt => "interrupt",
+            ServiceAction::Quit => "quit",
+            ServiceAction::Usr1 => "1",
+            ServiceAction::Usr2 => "2",
+            ServiceAction::Term => "term",
+            ServiceAction::Kill => "kill",
+            ServiceAction::Exit => "exit",
+            ServiceAction::Check => "check",
+            ServiceAction::ForceStop => "force-stop",
+            ServiceAction::ForceRestart => "force-restart",
+            ServiceAction::ForceShutdown => "force-shutdown",
+        }
+    }
+
+    pub fn from_str(action: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|candidate| candidate.as_str() == action)
+    }
+
+    /// The `sv` subcommand implementing this action, `None` for actions handled without `sv`.
+    fn sv_command(&self) -> Option<&'static str> {
+        match self {
+            ServiceAction::Start => Some("up"),
+            ServiceAction::Stop => Some("down"),
+            ServiceAction::Enable | ServiceAction::Disable => None,
+            action => Some(action.as_str()),
         }
     }
+
+    fn done_message(&self, service_name: &str) -> String {
+        let done = match self {
+            ServiceAction::Start => "started",
+            ServiceAction::Stop => "stopped",
+            ServiceAction::Restart => "restarted",
+            ServiceAction::Enable => "enabled",
+            ServiceAction::Disable => "disabled",
+            ServiceAction::Once => "started once",
+            ServiceAction::Pause => "paused",
+            ServiceAction::Cont => "continued",
+            ServiceAction::Hup => "sent HUP",
+            ServiceAction::Alarm => "sent ALRM",
+            ServiceAction::Interrupt => "sent INT",
+            ServiceAction::Quit => "sent QUIT",
+            ServiceAction::Usr1 => "sent USR1",
+            ServiceAction::Usr2 => "sent USR2",
+            ServiceAction::Term => "sent TERM",
+            ServiceAction::Kill => "sent KILL",
+            ServiceAction::Exit => "exited",
+            ServiceAction::Check => "checked",
+            ServiceAction::ForceStop => "force-stopped",
+            ServiceAction::ForceRestart => "force-restarted",
+            ServiceAction::ForceShutdown => "force-shut down",
+        };
+        format!("Service {} {}.", service_name, done)
+    }
+}
+
+/// Outcome of a successful action, with the output of `sv` when it was invoked.
+#[derive(Debug, Default)]
+pub struct ActionOutput {
+    pub message: String,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+impl ActionOutput {
+    fn new(message: String, output: Option<Output>) -> Self {
+        let (stdout, stderr) = output
+            .map(|output| {
+                (
+                    String::from_utf8_lossy(&output.stdout).into_owned(),
+                    String::from_utf8_lossy(&output.stderr).into_owned(),
+                )
+            })
+            .unwrap_or_default();
+        Self { message, stdout, stderr }
+    }
 }
 
-fn execute_command(command: &mut Command) -> Result<Output, io::Error> {
-    let output = command.output()?;
+/// Extra time given to `sv -w` beyond its own timeout before it is killed.
+const SV_TIMEOUT_GRACE: Duration = Duration::from_secs(5);
+
+/// Runs `sv -w timeout`, killing it if it outlives its own timeout, so a hung `sv`
+/// can't hold a request forever.
+async fn execute_command(service_name: &str, command: &mut Command, timeout: u64) -> Result<Output, AppError> {
+    let limit = Duration::from_secs(timeout) + SV_TIMEOUT_GRACE;
+    let output = tokio::time::timeout(limit, command.kill_on_drop(true).output())
+        .await
+        .map_err(|_| AppError::Timeout(format!("{:?} took longer than {}s", command.as_std(), limit.as_secs())))?
+        .map_err(|e| AppError::io(format!("Failed to execute {:?}", command.as_std()), e))?;
     if output.status.success() {
         Ok(output)
     } else {
-        Err(io::Error::new(
-            io::ErrorKind::Other,
-            format!(
+        Err(AppError::SvFailure {
+            service: service_name.to_string(),
+            message: format!(
                 "Command execution failed: {:?} with status: {}",
-                command,
+                command.as_std(),
                 output.status
             ),
-        ))
+            stdout: String::from_utf8_lossy(&output.stdout).into_owned(),
+            stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+        })
     }
 }
 
-pub fn perform_service_action(service_name: &str, action: &str) -> Result<String, Box<dyn std::error::Error>> {
-    let action = ServiceAction::from_str(action);
-
+/// Performs `action` on the service. `timeout` is passed to `sv -w` and bounds how long
+/// `sv` waits for the service to reach the requested state.
+pub async fn perform_service_action(layout: &RunitLayout, service_name: &ServiceName, action: ServiceAction, timeout: u64) -> Result<ActionOutput, AppError> {
     match action {
-        Some(ServiceAction::Start) => {
-            execute_command(Command::new("sv").arg("up").arg(service_name))?;
-            Ok(format!("Service {} started.", service_name))
-        },
-        Some(ServiceAction::Stop) => {
-            execute_command(Command::new("sv").arg("down").arg(service_name))?;
-            Ok(format!("Service {} stopped.", service_name))
-        },
-        Some(ServiceAction::Restart) => {
-            execute_command(Command::new("sv").arg("restart").arg(service_name))?;
-            Ok(format!("Service {} restarted.", service_name))
+        ServiceAction::Enable => {
+            let source = service_name.definition_dir(layout)?;
+            let target = service_name.active_link(layout);
+            tokio::fs::symlink(&source, &target)
+                .await
+                .map_err(|e| AppError::io(format!("Failed to link {} to {}", target.display(), source.display()), e))?;
+            Ok(ActionOutput::new(action.done_message(service_name), None))
         },
-        Some(ServiceAction::Enable) => {
-            let source = format!("/etc/sv/{}", service_name);
-            let target = format!("/etc/service/{}", service_name);
-            symlink(source, target)?;
-            Ok(format!("Service {} enabled.", service_name))
+        ServiceAction::Disable => {
+            let target = service_name.active_link(layout);
+            tokio::fs::remove_file(&target)
+                .await
+                .map_err(|e| AppError::io(format!("Failed to remove {}", target.display()), e))?;
+            Ok(ActionOutput::new(action.done_message(service_name), None))
         },
-        Some(ServiceAction::Disable) => {
-            let target = format!("/etc/service/{}", service_name);
-            remove_file(target)?;
-            Ok(format!("Service {} disabled.", service_name))
+        _ => {
+            let sv_command = action.sv_command().expect("sv backed action");
+            let output = execute_command(
+                service_name,
+                Command::new("sv")
+                    .env("SVDIR", &layout.active_dir)
+                    .arg("-w")
+                    .arg(timeout.to_string())
+                    .arg(sv_command)
+                    .arg(service_name.as_str()),
+                timeout,
+            )
+            .await?;
+            Ok(ActionOutput::new(action.done_message(service_name), Some(output)))
         },
-        None => Err(Box::<dyn std::error::Error>::from("Invalid action")),
+    }
+}
+
+/// Writes a validated svlogd config for the service and sends HUP to its logger, which
+/// makes svlogd reread it. A stopped logger picks the config up when it starts.
+pub async fn update_log_config(service_info: &ServiceInfo, svlogd_config: SvlogdConfig, timeout: u64) -> Result<ActionOutput, AppError> {
+    svlogd_config.validate().map_err(AppError::InvalidConfig)?;
+
+    let log_dir = service_logs::editable_svlogd_directory(service_info)?.to_path_buf();
+    let operation = format!("writing {}/config", log_dir.display());
+    run_blocking(&operation, Duration::from_secs(timeout), move || {
+        svlogd_config.write(&log_dir)
+            .map_err(|e| AppError::io(format!("Failed to write {}/config", log_dir.display()), e))
+    })
+    .await?;
+    let message = format!("Log config of service {} updated.", service_info.name);
+
+    match &service_info.log {
+        Some(log) if log.is_running() => {
+            let output = execute_command(
+                &service_info.name,
+                Command::new("sv")
+                    .arg("-w")
+                    .arg(timeout.to_string())
+                    .arg("hup")
+                    .arg(&log.directory),
+                timeout,
+            )
+            .await?;
+            Ok(ActionOutput::new(message, Some(output)))
+        }
+        _ => Ok(ActionOutput::new(message, None)),
     }
 }
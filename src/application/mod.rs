@@ -1,3 +1,10 @@
+pub mod access_control;
+pub mod audit;
+pub mod auth;
+pub mod blocking;
+pub mod flapping;
 pub mod installer;
 pub mod manage_service;
 pub mod service_info;
+pub mod state_cache;
+pub mod state_events;
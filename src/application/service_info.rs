@@ -1,32 +1,96 @@
-use std::process::Command;
-use std::error::Error;
+use std::path::{Path, PathBuf};
 use serde::Serialize;
-use log::{error, warn};
+use log::warn;
 
-#[derive(Serialize)]
+use crate::application::blocking::run_blocking;
+use crate::config::app_config::AppConfig;
+use crate::config::layout::RunitLayout;
+use crate::error::AppError;
+use crate::domain::log_source::{self, LogTarget};
+use crate::domain::service::Registration;
+use crate::domain::service_name::ServiceName;
+use crate::domain::network_ports::{self, ListeningSocket, SocketTable};
+use crate::domain::service_tree::ProcessEntry;
+use crate::domain::supervise_status::{SuperviseStatus, Want};
+
+/// runit state flags beyond the plain run state, as reported by `sv status`.
+#[derive(Serialize, Debug, Default, Clone)]
+pub struct StateFlags {
+    /// The service directory has no `down` file, so runsv starts it on boot.
+    pub normally_up: bool,
+    pub want: Option<Want>,
+    pub paused: bool,
+    pub got_term: bool,
+}
+
+impl StateFlags {
+    fn from_status(service_dir: &Path, status: Option<&SuperviseStatus>) -> Self {
+        Self {
+            normally_up: !service_dir.join("down").exists(),
+            want: status.and_then(|s| s.want),
+            paused: status.is_some_and(|s| s.paused),
+            got_term: status.is_some_and(|s| s.got_term),
+        }
+    }
+}
+
+#[derive(Serialize, Clone)]
 pub struct LogInfo {
     pub name: String,
     pub status: String,
     pub pid: Option<u32>,
     pub uptime: Option<u64>,
+    /// The log service directory, `<service>/log`.
+    #[serde(skip)]
+    pub directory: PathBuf,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, Clone)]
 pub struct ServiceInfo {
     pub name: String,
     pub status: String,
     pub pid: Option<u32>,
     pub uptime: Option<u64>,
+    pub flags: StateFlags,
+    pub registration: Registration,
+    pub ports: Vec<ListeningSocket>,
+    /// Restarts seen within the flapping window; only tracked for cached states.
+    pub restarts: usize,
+    /// The service restarts too often to be considered healthy, however long it stays in `run`.
+    pub flapping: bool,
+    /// The service was taken down for flapping and hasn't been brought up since.
+    pub stopped_for_flapping: bool,
     pub log: Option<LogInfo>,
+    /// Where the service's output ends up, whether or not its logger is running.
+    pub log_target: Option<LogTarget>,
 }
 
 impl LogInfo {
-    pub fn new(name: String, status: String, pid: Option<u32>, uptime: Option<u64>) -> Self {
+    pub fn new(name: String, status: String, pid: Option<u32>, uptime: Option<u64>, directory: PathBuf) -> Self {
         Self {
             name,
             status,
             pid,
             uptime,
+            directory,
+        }
+    }
+
+    /// Reads the log service in `directory`; without runsv its status file is stale, so it is reported down.
+    fn read(directory: PathBuf, supervised: bool) -> Option<Self> {
+        if !directory.is_dir() {
+            return None;
+        }
+
+        match SuperviseStatus::read(&directory).ok().filter(|_| supervised) {
+            Some(status) => Some(Self::new(
+                "log".to_string(),
+                status.state.as_str().to_string(),
+                status.pid,
+                Some(status.uptime()),
+                directory,
+            )),
+            None => Some(Self::new("log".to_string(), "down".to_string(), None, None, directory)),
         }
     }
 
@@ -34,50 +98,35 @@ impl LogInfo {
         self.status == "run"
     }
 
+    /// Log location from the running logger's arguments, falling back to parsing
+    /// `log/run` so logs stay reachable after the logger died.
+    pub fn log_target(&self) -> Option<LogTarget> {
+        self.running_logger_target()
+            .or_else(|| log_source::run_script_target(&self.directory))
+    }
+
     pub fn log_directory(&self) -> Option<String> {
+        match self.log_target()? {
+            LogTarget::Directory { path, .. } => Some(path.to_string_lossy().into_owned()),
+            LogTarget::File { .. } => None,
+        }
+    }
+
+    fn running_logger_target(&self) -> Option<LogTarget> {
         if !self.is_running() {
             return None;
         }
 
         let pid = self.pid?;
-        let cmdline_path = format!("/proc/{}/comm", pid);
-
-        let cmdline = std::fs::read_to_string(&cmdline_path)
-            .map_err(|e| {
-                log::warn!(
-                    "Failed to read cmdline for pid {}: {}",
-                    pid,
-                    e
-                );
-                e
-            })
-            .ok()?;
-
-        if cmdline.trim() == "svlogd" {
-            self.svlogd_log_directory()
-        } else {
-            None
-        }
-    }
-
-    pub fn svlogd_log_directory(&self) -> Option<String> {
-        let log_file_path = format!("/proc/{}/cmdline", self.pid?);
-        let cmdline = std::fs::read_to_string(&log_file_path).map_err(|e| {
-            log::warn!(
-                "Failed to read svlogd log directory for pid {}: {}",
-                self.pid.unwrap(),
-                e
-            );
-            e
-        }).ok()?;
+        let Some(process) = ProcessEntry::read(pid) else {
+            warn!("Failed to read log process {}", pid);
+            return None;
+        };
+        // Relative log directories are resolved against the logger's working directory
+        let cwd = std::fs::read_link(format!("/proc/{}/cwd", pid)).unwrap_or_else(|_| self.directory.clone());
+        let args: Vec<&str> = process.cmdline.iter().map(String::as_str).collect();
 
-        Some(
-            cmdline
-                .split('\0')
-                .filter(|s| !s.is_empty())
-                .last()?
-                .to_string(),
-        )
+        log_source::logger_target(&args, &cwd)
     }
 
     pub fn as_json(&self) -> serde_json::Value {
@@ -93,16 +142,52 @@ impl LogInfo {
 
 impl ServiceInfo {
     // Constructor for creating a new ServiceInfo
-    pub fn new(name: String, status: String, pid: Option<u32>, uptime: Option<u64>, log: Option<LogInfo>) -> Self {
+    pub fn new(
+        name: String,
+        status: String,
+        pid: Option<u32>,
+        uptime: Option<u64>,
+        flags: StateFlags,
+        registration: Registration,
+        log: Option<LogInfo>,
+    ) -> Self {
         Self {
             name,
             status,
             pid,
             uptime,
+            flags,
+            ports: Vec::new(),
+            restarts: 0,
+            flapping: false,
+            stopped_for_flapping: false,
+            log_target: Self::resolve_log_target(&registration.directory, log.as_ref()),
+            registration,
             log,
         }
     }
 
+    /// An override file wins over the log service, then over output redirected by the run script.
+    fn resolve_log_target(service_dir: &Path, log: Option<&LogInfo>) -> Option<LogTarget> {
+        log_source::read_override(service_dir)
+            .or_else(|| log.and_then(LogInfo::log_target))
+            .or_else(|| log_source::run_script_target(service_dir))
+    }
+
+    /// Fills `ports` for a single service, without taking snapshots of every process.
+    pub fn read_ports(&mut self) {
+        if let Some(pid) = self.pid {
+            self.ports = network_ports::tree_ports(pid);
+        }
+    }
+
+    /// Fills `ports` from process and socket snapshots, which callers share across services.
+    pub fn attach_ports(&mut self, processes: &[ProcessEntry], sockets: &SocketTable) {
+        if let Some(pid) = self.pid {
+            self.ports = network_ports::service_ports(pid, processes, sockets);
+        }
+    }
+
     pub fn is_running(&self) -> bool {
         self.status == "run"
     }
@@ -115,51 +200,61 @@ impl ServiceInfo {
             "status": self.status,
             "pid": self.pid,
             "uptime": self.uptime,
+            "normally_up": self.flags.normally_up,
+            "want": self.flags.want,
+            "paused": self.flags.paused,
+            "got_term": self.flags.got_term,
+            "defined": self.registration.defined,
+            "enabled": self.registration.enabled,
+            "supervised": self.registration.supervised,
+            "broken_link": self.registration.broken_link,
+            "link_outside": self.registration.link_outside,
+            "ports": self.ports,
+            "restarts": self.restarts,
+            "flapping": self.flapping,
+            "stopped_for_flapping": self.stopped_for_flapping,
             "log": self.log.as_ref().map(|log| log.as_json()),
+            "log_target": self.log_target,
         })
     }
 
-    pub fn get_status(name: &str) -> Result<Self, Box<dyn Error>> {
-        let output = Command::new("sv")
-            .arg("status")
-            .arg(name)
-            .output()
-            .map_err(|e| {
-                error!("Failed to execute sv command for {}: {}", name, e);
-                e
-            })?;
-
-        if output.status.success() {
-            let output_str = String::from_utf8_lossy(&output.stdout);
-            let re = regex::Regex::new(
-                &format!(
-                    r"(?<status>[^:]+): {}:(?: \(pid (?<pid>\d+)\))? (?<uptime>\d+)s(?:, [^;]+)?(?:; (?<log_status>[^:]+): (?<log_name>[^:]+):(?: \(pid (?<log_pid>\d+)\))? (?<log_uptime>\d+)s)?",
-                    regex::escape(name)
-                ),
-            )?;
-            let captures = re.captures(&output_str).ok_or("Regex capture failed")?;
-            let status = captures.name("status").map(|m| m.as_str().to_string());
-            let pid = captures.name("pid").map(|m| m.as_str().parse::<u32>().unwrap());
-            let uptime = captures.name("uptime").map(|m| m.as_str().parse::<u64>().unwrap());
-            let log = captures.name("log_name").map(|log_name| {
-                LogInfo::new(
-                    log_name.as_str().to_string(),
-                    captures["log_status"].to_string(),
-                    captures.name("log_pid").and_then(|m| m.as_str().parse::<u32>().ok()),
-                    captures.name("log_uptime").and_then(|m| m.as_str().parse::<u64>().ok()),
-                )
-            });
-
-            Ok(ServiceInfo::new(
-                name.to_string(),
-                status.unwrap_or_else(|| "unknown".to_string()),
-                pid,
-                uptime,
-                log,
-            ))
-        } else {
-            warn!("Service is not running: {}", name);
-            Ok(ServiceInfo::new(name.to_string(), "down".to_string(), None, None, None))
-        }
+    pub fn get_status(layout: &RunitLayout, name: &ServiceName) -> Result<Self, AppError> {
+        let registration = Registration::inspect(layout, name)?
+            .ok_or_else(|| AppError::ServiceNotFound(name.to_string()))?;
+        let service_dir = registration.directory.clone();
+        // Kept even when the logger is down, since that is when its logs matter most
+        let log = LogInfo::read(service_dir.join("log"), registration.supervised);
+
+        // Without runsv the status file is stale and its pid may have been reused
+        let status = match registration.supervised.then(|| SuperviseStatus::read(&service_dir)) {
+            Some(Ok(status)) => status,
+            Some(Err(e)) => {
+                warn!("Failed to read status of {}: {}", name, e);
+                let flags = StateFlags::from_status(&service_dir, None);
+                return Ok(ServiceInfo::new(name.to_string(), "down".to_string(), None, None, flags, registration, log));
+            }
+            None => {
+                warn!("Service is not supervised: {}", name);
+                let flags = StateFlags::from_status(&service_dir, None);
+                return Ok(ServiceInfo::new(name.to_string(), "down".to_string(), None, None, flags, registration, log));
+            }
+        };
+
+        Ok(ServiceInfo::new(
+            name.to_string(),
+            status.state.as_str().to_string(),
+            status.pid,
+            Some(status.uptime()),
+            StateFlags::from_status(&service_dir, Some(&status)),
+            registration,
+            log,
+        ))
+    }
+
+    /// `get_status` on the blocking pool, bounded by the configured status timeout.
+    pub async fn fetch_status(config: &AppConfig, name: ServiceName) -> Result<Self, AppError> {
+        let layout = config.layout.clone();
+        let operation = format!("status of {}", name);
+        run_blocking(&operation, config.status_timeout(), move || Self::get_status(&layout, &name)).await
     }
 }
\ No newline at end of file
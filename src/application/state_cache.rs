@@ -0,0 +1,404 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::io;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+use futures_util::StreamExt;
+use inotify::{EventMask, EventStream, Inotify, WatchDescriptor, WatchMask};
+use log::{info, warn};
+
+use crate::application::audit::{AuditEntry, AuditLog};
+use crate::application::blocking::run_blocking;
+use crate::application::flapping::{FlapPolicy, RestartHistory};
+use crate::application::manage_service::{self, ServiceAction};
+use crate::application::service_info::ServiceInfo;
+use crate::application::state_events::EventJournal;
+use crate::config::app_config::AppConfig;
+use crate::config::layout::RunitLayout;
+use crate::domain::service;
+use crate::domain::service_name::ServiceName;
+use crate::error::AppError;
+
+/// How long changes are collected before the affected services are re-read; runsv
+/// rewrites `supervise/status` several times while a service changes state.
+const SETTLE_DELAY: Duration = Duration::from_millis(100);
+
+/// Action name recorded in the audit log when a flapping service is taken down.
+const FLAPPING_STOP_AUDIT_ACTION: &str = "flapping-stop";
+
+struct CachedService {
+    info: ServiceInfo,
+    read_at: Instant,
+    history: RestartHistory,
+}
+
+impl CachedService {
+    /// The cached state with uptimes and restarts counted up to now.
+    fn current(&self, policy: &FlapPolicy) -> ServiceInfo {
+        let elapsed = self.read_at.elapsed().as_secs();
+        let mut info = self.info.clone();
+        info.uptime = info.uptime.map(|uptime| uptime + elapsed);
+        if let Some(log) = &mut info.log {
+            log.uptime = log.uptime.map(|uptime| uptime + elapsed);
+        }
+        self.history.annotate(&mut info, policy);
+        info
+    }
+}
+
+/// The cached services, along with which read each was last updated from.
+#[derive(Default)]
+struct Services {
+    states: BTreeMap<ServiceName, CachedService>,
+    /// The latest read applied for each service, including reads that found it gone.
+    applied: HashMap<ServiceName, u64>,
+}
+
+impl Services {
+    /// Whether a read started later than `read` was applied already. Reads run unlocked and
+    /// may finish out of order; storing an older state over a newer one would report changes
+    /// that never happened, such as the pid flipping back and forth.
+    fn is_outdated(&self, name: &ServiceName, read: u64) -> bool {
+        self.applied.get(name).is_some_and(|&applied| applied > read)
+    }
+}
+
+/// Service states kept in memory and updated when runit changes them, so listing
+/// services doesn't re-read every service on each request.
+pub struct StateCache {
+    config: AppConfig,
+    flap_policy: FlapPolicy,
+    audit_log: Arc<AuditLog>,
+    services: RwLock<Services>,
+    /// Number of the latest read started.
+    reads: AtomicU64,
+    events: EventJournal,
+}
+
+impl StateCache {
+    pub fn new(config: AppConfig, audit_log: Arc<AuditLog>) -> Self {
+        Self {
+            flap_policy: FlapPolicy::from_config(&config),
+            audit_log,
+            config,
+            services: RwLock::new(Services::default()),
+            reads: AtomicU64::new(0),
+            events: EventJournal::default(),
+        }
+    }
+
+    /// Changes seen while updating the cache.
+    pub fn events(&self) -> &EventJournal {
+        &self.events
+    }
+
+    /// All cached services ordered by name, with the sequence number of the latest event
+    /// they reflect, from which a client can follow changes.
+    pub fn list(&self) -> (Vec<ServiceInfo>, u64) {
+        let services = self.services.read().expect("state cache lock poisoned");
+        // Events are published under the write lock, so the sequence matches the list
+        let sequence = self.events.last_sequence();
+        (services.states.values().map(|cached| cached.current(&self.flap_policy)).collect(), sequence)
+    }
+
+    /// The cached state of a service, read directly when it isn't cached or is older than `stale_after`.
+    pub async fn get(&self, name: ServiceName) -> Result<ServiceInfo, AppError> {
+        let cached = {
+            let services = self.services.read().expect("state cache lock poisoned");
+            services
+                .states
+                .get(&name)
+                .filter(|cached| cached.read_at.elapsed() < self.config.stale_after())
+                .map(|cached| cached.current(&self.flap_policy))
+        };
+
+        match cached {
+            Some(info) => Ok(info),
+            None => self.refresh(name).await,
+        }
+    }
+
+    /// Re-reads a service, dropping it from the cache once runit no longer knows it.
+    pub async fn refresh(&self, name: ServiceName) -> Result<ServiceInfo, AppError> {
+        let read = self.start_read();
+        let layout = self.config.layout.clone();
+        let operation = format!("status of {}", name);
+        let result = {
+            let name = name.clone();
+            run_blocking(&operation, self.config.status_timeout(), move || {
+                let mut info = ServiceInfo::get_status(&layout, &name)?;
+                info.read_ports();
+                Ok(info)
+            })
+            .await
+        };
+
+        let mut services = self.services.write().expect("state cache lock poisoned");
+        if services.is_outdated(&name, read) {
+            // A later read finished first, and its state stands
+            return match services.states.get(&name) {
+                Some(cached) => Ok(cached.current(&self.flap_policy)),
+                None => Err(AppError::ServiceNotFound(name.to_string())),
+            };
+        }
+        match result {
+            Ok(info) => {
+                self.store(&mut services, name.clone(), read, info);
+                Ok(services.states[&name].current(&self.flap_policy))
+            }
+            Err(e) => {
+                if let AppError::ServiceNotFound(_) = e {
+                    self.evict(&mut services, &name, read);
+                }
+                // Otherwise keep the last known state rather than hiding a service that is slow to read
+                Err(e)
+            }
+        }
+    }
+
+    /// Numbers a read about to start, so its result can be ordered against others.
+    fn start_read(&self) -> u64 {
+        self.reads.fetch_add(1, Ordering::Relaxed) + 1
+    }
+
+    /// Caches a fresh read of a service, carrying its restart history over from the previous
+    /// read, and takes it down when it restarted `stop_after` times.
+    fn store(&self, services: &mut Services, name: ServiceName, read: u64, mut info: ServiceInfo) {
+        let previous = services.states.remove(&name);
+        let mut history = previous.as_ref().map(|previous| previous.history.clone()).unwrap_or_default();
+        history.record(&info, &self.flap_policy);
+        history.annotate(&mut info, &self.flap_policy);
+        if history.take_stop(&info, &self.flap_policy) {
+            self.stop_flapping(name.clone(), info.restarts);
+        }
+
+        self.events.publish(&name, previous.as_ref().map(|previous| &previous.info), Some(&info));
+        services.applied.insert(name.clone(), read);
+        services.states.insert(name, CachedService { info, read_at: Instant::now(), history });
+    }
+
+    /// Drops a service runit no longer knows.
+    fn evict(&self, services: &mut Services, name: &ServiceName, read: u64) {
+        services.applied.insert(name.clone(), read);
+        if let Some(removed) = services.states.remove(name) {
+            self.events.publish(name, Some(&removed.info), None);
+        }
+    }
+
+    /// Runs `sv down` for a flapping service in the background, recording it in the audit log.
+    fn stop_flapping(&self, name: ServiceName, restarts: usize) {
+        warn!(
+            "Service {} restarted {} times within {}s, taking it down",
+            name,
+            restarts,
+            self.flap_policy.window.as_secs()
+        );
+        let layout = self.config.layout.clone();
+        let timeout = self.config.sv_timeout;
+        let audit_log = self.audit_log.clone();
+
+        actix_web::rt::spawn(async move {
+            let result = manage_service::perform_service_action(&layout, &name, ServiceAction::Stop, timeout).await;
+            let entry = AuditEntry::new(None, None, &name, FLAPPING_STOP_AUDIT_ACTION, &result);
+            audit_log.record_async(entry).await;
+            if let Err(e) = result {
+                warn!("Failed to take down flapping service {}: {}", name, e);
+            }
+        });
+    }
+
+    /// Re-reads every service, replacing the whole cache. A service that is slow or fails to
+    /// read keeps its last known state; only services runit no longer knows are dropped.
+    async fn refresh_all(&self) -> Vec<ServiceInfo> {
+        let read = self.start_read();
+        let list = match service::fetch_service_list(&self.config).await {
+            Ok(list) => Some(list),
+            Err(e) => {
+                warn!("Failed to list services, keeping their last known states: {}", e);
+                None
+            }
+        };
+
+        let mut services = self.services.write().expect("state cache lock poisoned");
+        if let Some(list) = list {
+            let listed: BTreeSet<ServiceName> = list.iter().map(|(name, _)| name.clone()).collect();
+            for (name, result) in list {
+                if services.is_outdated(&name, read) {
+                    continue;
+                }
+                match result {
+                    Ok(info) => self.store(&mut services, name, read, info),
+                    Err(AppError::ServiceNotFound(_)) => self.evict(&mut services, &name, read),
+                    Err(e) => warn!("Failed to refresh service state: {}", e),
+                }
+            }
+
+            // Services gone from both service directories, unless a later read found them again
+            let gone: Vec<ServiceName> = services
+                .states
+                .keys()
+                .filter(|name| !listed.contains(*name) && !services.is_outdated(name, read))
+                .cloned()
+                .collect();
+            for name in gone {
+                self.evict(&mut services, &name, read);
+            }
+        }
+        services.states.values().map(|cached| cached.info.clone()).collect()
+    }
+
+    /// Keeps the cache up to date: services are re-read when inotify reports changes to their
+    /// directories, and all of them every `stale_after`, which is all that happens where
+    /// inotify is unavailable.
+    pub async fn run(self: Arc<Self>) {
+        let mut watcher = match StateWatcher::new(&self.config.layout) {
+            Ok(watcher) => Some(watcher),
+            Err(e) => {
+                warn!("inotify unavailable, polling service states every {}s: {}", self.config.stale_after, e);
+                None
+            }
+        };
+        let mut poll = tokio::time::interval(self.config.stale_after());
+        let mut changed = BTreeSet::new();
+        let mut settle_at = tokio::time::Instant::now();
+
+        loop {
+            tokio::select! {
+                _ = poll.tick() => {
+                    let list = self.refresh_all().await;
+                    if let Some(watcher) = &mut watcher {
+                        list.iter().for_each(|info| watcher.watch_service(info));
+                    }
+                }
+                event = next_event(&mut watcher) => match event {
+                    WatchEvent::Changed(name) => {
+                        if changed.is_empty() {
+                            settle_at = tokio::time::Instant::now() + SETTLE_DELAY;
+                        }
+                        changed.insert(name);
+                    }
+                    WatchEvent::Overflow => poll.reset_immediately(),
+                    WatchEvent::Ignored => {}
+                    WatchEvent::Failed(e) => {
+                        warn!("Watching service states failed, falling back to polling: {}", e);
+                        watcher = None;
+                    }
+                },
+                _ = tokio::time::sleep_until(settle_at), if !changed.is_empty() => {
+                    for name in std::mem::take(&mut changed) {
+                        match self.refresh(name).await {
+                            Ok(info) => {
+                                if let Some(watcher) = &mut watcher {
+                                    watcher.watch_service(&info);
+                                }
+                            }
+                            Err(AppError::ServiceNotFound(name)) => info!("Service removed: {}", name),
+                            Err(e) => warn!("Failed to refresh service state: {}", e),
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// What an inotify event means for the cache.
+enum WatchEvent {
+    /// Something changed for the named service.
+    Changed(ServiceName),
+    /// Events were dropped, so any service may have changed.
+    Overflow,
+    /// An event that doesn't affect any service.
+    Ignored,
+    /// The event stream failed.
+    Failed(io::Error),
+}
+
+async fn next_event(watcher: &mut Option<StateWatcher>) -> WatchEvent {
+    match watcher {
+        Some(watcher) => watcher.next().await,
+        None => std::future::pending().await,
+    }
+}
+
+/// inotify watches on the service directories and on each service's directory and
+/// `supervise` directories.
+struct StateWatcher {
+    events: EventStream<[u8; 4096]>,
+    /// The definition and active directories, whose entries are services.
+    roots: HashSet<WatchDescriptor>,
+    services: HashMap<WatchDescriptor, ServiceName>,
+}
+
+impl StateWatcher {
+    fn new(layout: &RunitLayout) -> io::Result<Self> {
+        let events = Inotify::init()?.into_event_stream([0; 4096])?;
+        let mut roots = HashSet::new();
+        let mask = WatchMask::CREATE | WatchMask::DELETE | WatchMask::MOVED_FROM | WatchMask::MOVED_TO;
+        for dir in [&layout.services_dir, &layout.active_dir] {
+            roots.insert(events.watches().add(dir, mask)?);
+        }
+
+        Ok(Self { events, roots, services: HashMap::new() })
+    }
+
+    /// Watches the files runit changes for a service: `down` and `log` in its directory,
+    /// and the status files runsv writes into `supervise` and `log/supervise`.
+    fn watch_service(&mut self, info: &ServiceInfo) {
+        let Ok(name) = ServiceName::parse(&info.name) else {
+            return;
+        };
+        let directory = &info.registration.directory;
+        let entries = WatchMask::CREATE | WatchMask::DELETE | WatchMask::MOVED_FROM | WatchMask::MOVED_TO;
+        // runsv renames `status.new` over `status`; writes would also catch our own probes of `supervise/ok`
+        let status = WatchMask::MOVED_TO;
+
+        for (path, mask) in [
+            (directory.clone(), entries),
+            (directory.join("supervise"), status),
+            (directory.join("log"), entries),
+            (directory.join("log").join("supervise"), status),
+        ] {
+            if let Some(wd) = self.add(&path, mask) {
+                self.services.insert(wd, name.clone());
+            }
+        }
+    }
+
+    fn add(&mut self, path: &Path, mask: WatchMask) -> Option<WatchDescriptor> {
+        match self.events.watches().add(path, mask) {
+            Ok(wd) => Some(wd),
+            // A service without a logger or not yet started by runsv lacks some of them
+            Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+            Err(e) => {
+                warn!("Failed to watch {}: {}", path.display(), e);
+                None
+            }
+        }
+    }
+
+    async fn next(&mut self) -> WatchEvent {
+        let event = match self.events.next().await {
+            Some(Ok(event)) => event,
+            Some(Err(e)) => return WatchEvent::Failed(e),
+            None => return WatchEvent::Failed(io::Error::other("inotify event stream ended")),
+        };
+
+        if event.mask.contains(EventMask::Q_OVERFLOW) {
+            return WatchEvent::Overflow;
+        }
+        if event.mask.contains(EventMask::IGNORED) {
+            // The watched directory is gone; the service is re-read through its parent's event
+            self.services.remove(&event.wd);
+            return WatchEvent::Ignored;
+        }
+
+        let name = if self.roots.contains(&event.wd) {
+            event.name.and_then(|entry| ServiceName::parse(&entry.to_string_lossy()).ok())
+        } else {
+            self.services.get(&event.wd).cloned()
+        };
+        name.map_or(WatchEvent::Ignored, WatchEvent::Changed)
+    }
+}
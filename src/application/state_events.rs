@@ -0,0 +1,266 @@
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+use log::warn;
+use serde::Serialize;
+use tokio::sync::broadcast;
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::application::service_info::ServiceInfo;
+
+/// Events kept for clients that reconnect and replay from a sequence number.
+const EVENT_HISTORY: usize = 1000;
+/// Events buffered for each connected client before it is told to resync.
+const EVENT_BACKLOG: usize = 256;
+
+/// A change in the state of a service, as seen between two reads of it.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ServiceChange {
+    Added,
+    Removed,
+    /// The run state changed, e.g. from `run` to `down`.
+    StateChanged { from: String, to: String },
+    /// The service is running under a new process, so it restarted in between.
+    PidChanged { from: u32, to: u32 },
+    /// A `down` file was created or removed.
+    NormallyUpChanged { normally_up: bool },
+    Enabled,
+    Disabled,
+    /// The log service changed run state, e.g. died with `to` being `down`.
+    LogStateChanged { from: String, to: String },
+    /// The service started or stopped restarting too often, or was taken down for it.
+    FlappingChanged { flapping: bool, restarts: usize, stopped_for_flapping: bool },
+}
+
+/// Compares two reads of a service; `None` means the service isn't known.
+pub fn service_changes(old: Option<&ServiceInfo>, new: Option<&ServiceInfo>) -> Vec<ServiceChange> {
+    let (old, new) = match (old, new) {
+        (None, None) => return Vec::new(),
+        (None, Some(_)) => return vec![ServiceChange::Added],
+        (Some(_), None) => return vec![ServiceChange::Removed],
+        (Some(old), Some(new)) => (old, new),
+    };
+
+    let mut changes = Vec::new();
+    if old.status != new.status {
+        changes.push(ServiceChange::StateChanged { from: old.status.clone(), to: new.status.clone() });
+    }
+    if let (Some(from), Some(to)) = (old.pid, new.pid) {
+        if from != to {
+            changes.push(ServiceChange::PidChanged { from, to });
+        }
+    }
+    if old.flags.normally_up != new.flags.normally_up {
+        changes.push(ServiceChange::NormallyUpChanged { normally_up: new.flags.normally_up });
+    }
+    if old.registration.enabled != new.registration.enabled {
+        changes.push(if new.registration.enabled { ServiceChange::Enabled } else { ServiceChange::Disabled });
+    }
+
+    if old.flapping != new.flapping || old.stopped_for_flapping != new.stopped_for_flapping {
+        changes.push(ServiceChange::FlappingChanged {
+            flapping: new.flapping,
+            restarts: new.restarts,
+            stopped_for_flapping: new.stopped_for_flapping,
+        });
+    }
+
+    let log_status = |info: &ServiceInfo| info.log.as_ref().map(|log| log.status.clone());
+    if let (Some(from), Some(to)) = (log_status(old), log_status(new)) {
+        if from != to {
+            changes.push(ServiceChange::LogStateChanged { from, to });
+        }
+    }
+    changes
+}
+
+/// A numbered service change, with the state of the service after it.
+#[derive(Serialize, Debug, Clone)]
+pub struct ServiceEvent {
+    pub sequence: u64,
+    /// Seconds since the UNIX epoch.
+    pub timestamp: u64,
+    pub service: String,
+    #[serde(flatten)]
+    pub change: ServiceChange,
+    /// The service JSON after the change; `null` once it was removed.
+    pub state: Option<serde_json::Value>,
+}
+
+/// What a client catching up from a sequence number gets before following live events.
+pub enum Replay {
+    /// The events the client missed, possibly none.
+    Events(Vec<ServiceEvent>),
+    /// The missed events are no longer kept; the client must reload the full state.
+    Resync,
+}
+
+/// What a subscriber gets next from the live events.
+#[derive(Debug)]
+pub enum LiveEvent {
+    Event(ServiceEvent),
+    /// The subscriber fell more than `EVENT_BACKLOG` events behind and missed some.
+    Resync,
+}
+
+/// Waits for the next live event, `None` once the journal is gone.
+pub async fn next_live(receiver: &mut broadcast::Receiver<ServiceEvent>) -> Option<LiveEvent> {
+    match receiver.recv().await {
+        Ok(event) => Some(LiveEvent::Event(event)),
+        Err(RecvError::Lagged(missed)) => {
+            warn!("Event stream client fell {} events behind", missed);
+            Some(LiveEvent::Resync)
+        }
+        Err(RecvError::Closed) => None,
+    }
+}
+
+struct History {
+    last_sequence: u64,
+    events: VecDeque<ServiceEvent>,
+}
+
+/// Numbers service changes, keeps the latest for replay and broadcasts them to subscribers.
+pub struct EventJournal {
+    history: Mutex<History>,
+    sender: broadcast::Sender<ServiceEvent>,
+}
+
+impl Default for EventJournal {
+    fn default() -> Self {
+        Self {
+            history: Mutex::new(History { last_sequence: 0, events: VecDeque::new() }),
+            sender: broadcast::channel(EVENT_BACKLOG).0,
+        }
+    }
+}
+
+impl EventJournal {
+    /// Sequence number of the latest event, 0 before the first.
+    pub fn last_sequence(&self) -> u64 {
+        self.history.lock().expect("event journal lock poisoned").last_sequence
+    }
+
+    /// Records the changes between two reads of a service.
+    pub fn publish(&self, service: &str, old: Option<&ServiceInfo>, new: Option<&ServiceInfo>) {
+        let changes = service_changes(old, new);
+        if changes.is_empty() {
+            return;
+        }
+
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_secs());
+        let state = new.map(ServiceInfo::as_json);
+        let mut history = self.history.lock().expect("event journal lock poisoned");
+        for change in changes {
+            history.last_sequence += 1;
+            let event = ServiceEvent {
+                sequence: history.last_sequence,
+                timestamp,
+                service: service.to_string(),
+                change,
+                state: state.clone(),
+            };
+            if history.events.len() == EVENT_HISTORY {
+                history.events.pop_front();
+            }
+            history.events.push_back(event.clone());
+            // Nobody listening is fine
+            let _ = self.sender.send(event);
+        }
+    }
+
+    /// Subscribes to live events along with those after `after`; both are taken under
+    /// the same lock, so no event falls between the replay and the live ones.
+    pub fn subscribe(&self, after: Option<u64>) -> (Replay, broadcast::Receiver<ServiceEvent>) {
+        let history = self.history.lock().expect("event journal lock poisoned");
+        let receiver = self.sender.subscribe();
+
+        let replay = match after {
+            None => Replay::Events(Vec::new()),
+            // A client from before a restart, or from the future
+            Some(after) if after > history.last_sequence => Replay::Resync,
+            Some(after) => {
+                let oldest = history.events.front().map_or(history.last_sequence + 1, |event| event.sequence);
+                if after + 1 < oldest {
+                    Replay::Resync
+                } else {
+                    Replay::Events(history.events.iter().filter(|event| event.sequence > after).cloned().collect())
+                }
+            }
+        };
+        (replay, receiver)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::application::service_info::StateFlags;
+    use crate::domain::service::Registration;
+
+    /// Publishes `count` events, one `added` per service.
+    fn publish(journal: &EventJournal, count: usize) {
+        let info = ServiceInfo::new(
+            "demo".to_string(),
+            "run".to_string(),
+            Some(1),
+            Some(1),
+            StateFlags::default(),
+            Registration::default(),
+            None,
+        );
+        for index in 0..count {
+            journal.publish(&format!("demo{}", index), None, Some(&info));
+        }
+    }
+
+    fn sequences(replay: Replay) -> Option<Vec<u64>> {
+        match replay {
+            Replay::Events(events) => Some(events.iter().map(|event| event.sequence).collect()),
+            Replay::Resync => None,
+        }
+    }
+
+    #[test]
+    fn replays_the_events_after_the_last_id() {
+        let journal = EventJournal::default();
+        publish(&journal, 5);
+
+        assert_eq!(sequences(journal.subscribe(Some(3)).0), Some(vec![4, 5]));
+        assert_eq!(sequences(journal.subscribe(Some(5)).0), Some(vec![]));
+        assert_eq!(sequences(journal.subscribe(None).0), Some(vec![]));
+        // An id from before a restart of the server
+        assert_eq!(sequences(journal.subscribe(Some(6)).0), None);
+    }
+
+    #[test]
+    fn resyncs_once_the_id_left_the_history() {
+        let journal = EventJournal::default();
+        publish(&journal, EVENT_HISTORY + 10);
+        let last = journal.last_sequence();
+
+        let replayed = sequences(journal.subscribe(Some(10)).0).unwrap();
+        assert_eq!(replayed.len(), EVENT_HISTORY);
+        assert_eq!((replayed[0], replayed[EVENT_HISTORY - 1]), (11, last));
+        assert_eq!(sequences(journal.subscribe(Some(9)).0), None);
+    }
+
+    #[tokio::test]
+    async fn a_lagging_subscriber_is_told_to_resync() {
+        let journal = EventJournal::default();
+        let (_, mut receiver) = journal.subscribe(None);
+        publish(&journal, EVENT_BACKLOG + 1);
+
+        assert!(matches!(next_live(&mut receiver).await, Some(LiveEvent::Resync)));
+        // It then follows on from the oldest event still buffered
+        match next_live(&mut receiver).await {
+            Some(LiveEvent::Event(event)) => assert_eq!(event.sequence, 2),
+            other => panic!("expected an event, got {:?}", other),
+        }
+
+        drop(journal);
+        while let Some(LiveEvent::Event(_)) = next_live(&mut receiver).await {}
+        assert!(next_live(&mut receiver).await.is_none());
+    }
+}
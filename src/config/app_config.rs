@@ -1,8 +1,39 @@
+use std::time::Duration;
 use serde::Deserialize;
 
+use crate::config::layout::RunitLayout;
+
 #[derive(Deserialize, Debug, Clone)]
 pub struct AppConfig {
-    pub services_dir: String,
-    pub username: Option<String>,
-    pub password: Option<String>,
-}
\ No newline at end of file
+    pub layout: RunitLayout,
+    /// Seconds `sv -w` waits for a service to reach the requested state.
+    pub sv_timeout: u64,
+    /// Seconds a status query may take before the request fails.
+    pub status_timeout: u64,
+    /// Seconds reading, searching or packing logs may take before the request fails.
+    pub log_timeout: u64,
+    /// Services whose status is queried at the same time when listing them.
+    pub status_concurrency: usize,
+    /// Seconds after which cached service states are re-read, whether or not inotify reported a change.
+    pub stale_after: u64,
+    /// Seconds within which restarts of a service are counted.
+    pub flap_window: u64,
+    /// Restarts within the window from which a service is flapping.
+    pub flap_threshold: usize,
+    /// Restarts within the window after which a service is taken down; never when unset.
+    pub flap_stop_after: Option<usize>,
+}
+
+impl AppConfig {
+    pub fn status_timeout(&self) -> Duration {
+        Duration::from_secs(self.status_timeout)
+    }
+
+    pub fn log_timeout(&self) -> Duration {
+        Duration::from_secs(self.log_timeout)
+    }
+
+    pub fn stale_after(&self) -> Duration {
+        Duration::from_secs(self.stale_after)
+    }
+}
@@ -0,0 +1,88 @@
+use std::env;
+use std::path::Path;
+use clap::ValueEnum;
+use serde::Deserialize;
+
+/// Known runit directory conventions.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LayoutKind {
+    /// Pick a layout based on the directories present on this host.
+    Auto,
+    /// Debian and Ubuntu: `/etc/sv` linked into `/etc/service`.
+    Debian,
+    /// Alpine: same directories as Debian.
+    Alpine,
+    /// Void Linux: `/etc/sv` linked into `/var/service`.
+    Void,
+    /// Artix Linux: `/etc/runit/sv` linked into `/run/runit/service`.
+    Artix,
+    /// Per-user runsvdir: `~/sv` linked into `~/service`.
+    User,
+}
+
+/// Where service definitions live, where enabled services are linked for
+/// runsvdir, and where the installer puts the binary.
+#[derive(Deserialize, Debug, Clone)]
+pub struct RunitLayout {
+    /// Service definition directory, e.g. `/etc/sv`.
+    pub services_dir: String,
+    /// Directory watched by runsvdir, e.g. `/etc/service`.
+    pub active_dir: String,
+    /// Directory the installer copies the binary into.
+    pub bin_dir: String,
+}
+
+fn home_dir() -> String {
+    env::var("HOME").unwrap_or_else(|_| "/root".to_string())
+}
+
+fn is_root() -> bool {
+    // SAFETY: geteuid has no preconditions and cannot fail.
+    unsafe { libc::geteuid() == 0 }
+}
+
+impl RunitLayout {
+    pub fn for_kind(kind: LayoutKind) -> Self {
+        let (services_dir, active_dir, bin_dir) = match kind {
+            LayoutKind::Auto => return Self::detect(),
+            LayoutKind::Debian | LayoutKind::Alpine => ("/etc/sv".to_string(), "/etc/service".to_string(), "/usr/local/bin".to_string()),
+            LayoutKind::Void => ("/etc/sv".to_string(), "/var/service".to_string(), "/usr/local/bin".to_string()),
+            LayoutKind::Artix => ("/etc/runit/sv".to_string(), "/run/runit/service".to_string(), "/usr/local/bin".to_string()),
+            LayoutKind::User => {
+                let home = home_dir();
+                (format!("{}/sv", home), format!("{}/service", home), format!("{}/.local/bin", home))
+            }
+        };
+
+        Self {
+            services_dir,
+            active_dir,
+            bin_dir,
+        }
+    }
+
+    /// Guesses the layout from the runsvdir directories present on the host.
+    pub fn detect() -> Self {
+        let kind = if !is_root() && Path::new(&format!("{}/service", home_dir())).is_dir() {
+            LayoutKind::User
+        } else if Path::new("/run/runit/service").is_dir() {
+            LayoutKind::Artix
+        } else if Path::new("/var/service").is_dir() {
+            LayoutKind::Void
+        } else {
+            LayoutKind::Debian
+        };
+
+        Self::for_kind(kind)
+    }
+
+    /// Service definition directory of `name`.
+    pub fn service_dir(&self, name: &str) -> String {
+        format!("{}/{}", self.services_dir, name)
+    }
+
+    /// Path of the runsvdir link enabling `name`.
+    pub fn active_link(&self, name: &str) -> String {
+        format!("{}/{}", self.active_dir, name)
+    }
+}
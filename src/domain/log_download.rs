@@ -0,0 +1,224 @@
+use std::fs::{self, File};
+use std::io::{self, Read, Seek, Write};
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use log::info;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+use crate::application::service_info::ServiceInfo;
+use crate::domain::log_line::LogLine;
+use crate::domain::service_logs::{self, LogFile, TimeRange};
+use crate::error::AppError;
+
+/// Formats a service's logs can be downloaded in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DownloadFormat {
+    /// The svlogd files as they are on disk, in a gzipped tarball.
+    TarGz,
+    /// The svlogd files as they are on disk, in a zip archive.
+    Zip,
+    /// A single plain text file of the lines within the time range.
+    Log,
+}
+
+impl DownloadFormat {
+    pub fn from_str(value: &str) -> Option<Self> {
+        match value {
+            "tar.gz" | "tgz" => Some(Self::TarGz),
+            "zip" => Some(Self::Zip),
+            "log" => Some(Self::Log),
+            _ => None,
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::TarGz => "tar.gz",
+            Self::Zip => "zip",
+            Self::Log => "log",
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            Self::TarGz => "application/gzip",
+            Self::Zip => "application/zip",
+            Self::Log => "text/plain; charset=utf-8",
+        }
+    }
+}
+
+/// Keeps the files that may hold lines within `range`: each archive covers the time
+/// between the previous rotation and its own, and `current` everything since the last one.
+fn files_in_range(files: Vec<LogFile>, range: TimeRange) -> Vec<LogFile> {
+    let mut previous_rotation = None;
+
+    files
+        .into_iter()
+        .filter(|file| {
+            let starts_at = previous_rotation;
+            previous_rotation = file.rotated_at;
+            range.since.is_none_or(|since| file.rotated_at.is_none_or(|ends_at| ends_at >= since))
+                && range.until.is_none_or(|until| starts_at.is_none_or(|starts_at| starts_at <= until))
+        })
+        .collect()
+}
+
+/// Opens a file along with its current length, so a growing `current` is copied consistently.
+fn open_snapshot(file: &LogFile) -> io::Result<(File, fs::Metadata)> {
+    let handle = File::open(&file.path)?;
+    let metadata = handle.metadata()?;
+    Ok((handle, metadata))
+}
+
+fn file_name(file: &LogFile) -> String {
+    file.path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+fn write_tar_gz<W: Write>(service_name: &str, files: &[LogFile], writer: W) -> io::Result<()> {
+    let mut tar = tar::Builder::new(GzEncoder::new(writer, Compression::default()));
+
+    for file in files {
+        let (handle, metadata) = open_snapshot(file)?;
+        let mut header = tar::Header::new_gnu();
+        header.set_metadata(&metadata);
+        tar.append_data(&mut header, format!("{}/{}", service_name, file_name(file)), handle.take(metadata.len()))?;
+    }
+
+    tar.into_inner()?.finish()?;
+    Ok(())
+}
+
+fn write_zip<W: Write + Seek>(service_name: &str, files: &[LogFile], writer: W) -> io::Result<()> {
+    let mut zip = ZipWriter::new(writer);
+
+    for file in files {
+        let (handle, metadata) = open_snapshot(file)?;
+        let options = SimpleFileOptions::default()
+            .compression_method(CompressionMethod::Deflated)
+            .large_file(metadata.len() >= u32::MAX as u64);
+        zip.start_file(format!("{}/{}", service_name, file_name(file)), options)
+            .map_err(io::Error::other)?;
+        io::copy(&mut handle.take(metadata.len()), &mut zip)?;
+    }
+
+    zip.finish().map_err(io::Error::other)?;
+    Ok(())
+}
+
+fn write_log<W: Write>(files: &[LogFile], range: TimeRange, mut writer: W) -> io::Result<()> {
+    for file in files {
+        for line in service_logs::read_lines(&file.path)? {
+            if range.contains(LogLine::parse(&line).timestamp) {
+                writeln!(writer, "{}", line)?;
+            }
+        }
+    }
+
+    writer.flush()
+}
+
+/// Writes the logs of a service within `range` to `writer` in `format`.
+pub fn write_log_download<W: Write + Seek>(
+    service_info: &ServiceInfo,
+    range: TimeRange,
+    format: DownloadFormat,
+    writer: W,
+) -> Result<(), AppError> {
+    let files = service_logs::service_log_files(service_info)?;
+    write_files(&service_info.name, files, range, format, writer)
+        .map_err(|e| AppError::io(format!("Failed to pack logs of {}", service_info.name), e))
+}
+
+fn write_files<W: Write + Seek>(
+    service_name: &str,
+    files: Vec<LogFile>,
+    range: TimeRange,
+    format: DownloadFormat,
+    writer: W,
+) -> io::Result<()> {
+    let files = files_in_range(files, range);
+    info!("Packing {} log files of {} as {}", files.len(), service_name, format.extension());
+
+    match format {
+        DownloadFormat::TarGz => write_tar_gz(service_name, &files, writer),
+        DownloadFormat::Zip => write_zip(service_name, &files, writer),
+        DownloadFormat::Log => write_log(&files, range, io::BufWriter::new(writer)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use flate2::read::GzDecoder;
+    use crate::domain::log_source::{LogTarget, Logger};
+
+    /// Three archives rotated 100 seconds apart, then `current`, and a range from just after
+    /// the first rotation to just after the second: only the second and third archives can
+    /// hold lines within it.
+    fn download(format: DownloadFormat) -> Vec<u8> {
+        let dir = tempfile::tempdir().unwrap();
+        for (label, content) in [("65000000", "a1\n"), ("65000064", "a2\n"), ("650000c8", "a3\n")] {
+            fs::write(dir.path().join(format!("@40000000{}00000000.s", label)), content).unwrap();
+        }
+        fs::write(dir.path().join("current"), "c1\n").unwrap();
+
+        let files = service_logs::log_files(&LogTarget::Directory { path: dir.path().to_path_buf(), logger: Logger::Svlogd }).unwrap();
+        let range = TimeRange {
+            since: Some(files[0].rotated_at.unwrap() + 1.0),
+            until: Some(files[1].rotated_at.unwrap() + 1.0),
+        };
+
+        let mut output = Cursor::new(Vec::new());
+        write_files("demo", files, range, format, &mut output).unwrap();
+        output.into_inner()
+    }
+
+    const SELECTED: [(&str, &str); 2] = [
+        ("demo/@400000006500006400000000.s", "a2\n"),
+        ("demo/@40000000650000c800000000.s", "a3\n"),
+    ];
+
+    #[test]
+    fn tar_gz_holds_the_files_in_range() {
+        let archive = download(DownloadFormat::TarGz);
+        let mut tar = tar::Archive::new(GzDecoder::new(archive.as_slice()));
+        let entries: Vec<(String, String)> = tar
+            .entries()
+            .unwrap()
+            .map(|entry| {
+                let mut entry = entry.unwrap();
+                let path = entry.path().unwrap().display().to_string();
+                let mut content = String::new();
+                entry.read_to_string(&mut content).unwrap();
+                (path, content)
+            })
+            .collect();
+
+        assert_eq!(entries, SELECTED.map(|(path, content)| (path.to_string(), content.to_string())));
+    }
+
+    #[test]
+    fn zip_holds_the_files_in_range() {
+        let mut zip = zip::ZipArchive::new(Cursor::new(download(DownloadFormat::Zip))).unwrap();
+        let mut entries = Vec::new();
+        for index in 0..zip.len() {
+            let mut entry = zip.by_index(index).unwrap();
+            let mut content = String::new();
+            entry.read_to_string(&mut content).unwrap();
+            entries.push((entry.name().unwrap().into_owned(), content));
+        }
+
+        assert_eq!(entries, SELECTED.map(|(path, content)| (path.to_string(), content.to_string())));
+    }
+
+    #[test]
+    fn log_concatenates_the_files_in_range() {
+        assert_eq!(String::from_utf8(download(DownloadFormat::Log)).unwrap(), "a2\na3\n");
+    }
+}
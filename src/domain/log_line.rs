@@ -0,0 +1,142 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+use serde::Serialize;
+
+use crate::domain::tai64;
+
+/// A log line split into the svlogd timestamp prefix and the logged message.
+#[derive(Serialize, Debug, Clone)]
+pub struct LogLine {
+    /// Seconds since the UNIX epoch, `None` when the line carries no recognised timestamp.
+    pub timestamp: Option<f64>,
+    pub message: String,
+}
+
+impl LogLine {
+    /// Parses a line written by svlogd with `-t` (`@<tai64n>`), `-tt`
+    /// (`YYYY-MM-DD_HH:MM:SS.xxxxx`) or `-ttt` (`YYYY-MM-DDTHH:MM:SS.xxxxx`),
+    /// keeping lines without a timestamp as plain messages.
+    pub fn parse(line: &str) -> Self {
+        let parsed = line.split_once(' ').and_then(|(prefix, message)| {
+            let timestamp = if prefix.starts_with('@') {
+                tai64::parse_tai64n_label(prefix)
+            } else {
+                parse_utc_timestamp(prefix)
+            }?;
+            Some((timestamp, message))
+        });
+
+        match parsed {
+            Some((timestamp, message)) => Self {
+                timestamp: timestamp.duration_since(UNIX_EPOCH).ok().map(|d| d.as_secs_f64()),
+                message: message.to_string(),
+            },
+            None => Self {
+                timestamp: None,
+                message: line.to_string(),
+            },
+        }
+    }
+}
+
+/// Parses a time bound given either as seconds since the UNIX epoch or as a
+/// UTC `YYYY-MM-DDTHH:MM:SS[.fraction]` timestamp.
+pub fn parse_time_bound(value: &str) -> Option<f64> {
+    if let Ok(seconds) = value.parse::<f64>() {
+        return seconds.is_finite().then_some(seconds);
+    }
+
+    let value = value.strip_suffix('Z').unwrap_or(value);
+    parse_utc_timestamp(value)?
+        .duration_since(UNIX_EPOCH)
+        .ok()
+        .map(|d| d.as_secs_f64())
+}
+
+/// Days since 1970-01-01 of a proleptic Gregorian date.
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month = month as i64;
+    let day_of_year = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day as i64 - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// Parses `YYYY-MM-DD_HH:MM:SS.fraction` or the `T` separated form, both in UTC.
+fn parse_utc_timestamp(prefix: &str) -> Option<SystemTime> {
+    let bytes = prefix.as_bytes();
+    if bytes.len() < 19
+        || bytes[4] != b'-'
+        || bytes[7] != b'-'
+        || !matches!(bytes[10], b'_' | b'T')
+        || bytes[13] != b':'
+        || bytes[16] != b':'
+    {
+        return None;
+    }
+
+    let number = |range: std::ops::Range<usize>| -> Option<u32> {
+        let digits = prefix.get(range)?;
+        if digits.bytes().all(|b| b.is_ascii_digit()) {
+            digits.parse().ok()
+        } else {
+            None
+        }
+    };
+    let (year, month, day) = (number(0..4)?, number(5..7)?, number(8..10)?);
+    let (hour, minute, second) = (number(11..13)?, number(14..16)?, number(17..19)?);
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) || hour > 23 || minute > 59 || second > 60 {
+        return None;
+    }
+
+    let nanos = match prefix[19..].strip_prefix('.') {
+        Some(fraction) if !fraction.is_empty() && fraction.bytes().all(|b| b.is_ascii_digit()) => {
+            let digits = &fraction[..fraction.len().min(9)];
+            digits.parse::<u32>().ok()? * 10u32.pow(9 - digits.len() as u32)
+        }
+        None if prefix.len() == 19 => 0,
+        _ => return None,
+    };
+
+    let days = days_from_civil(year as i64, month, day);
+    let seconds = days * 86_400 + (hour * 3600 + minute * 60 + second) as i64;
+    UNIX_EPOCH.checked_add(std::time::Duration::new(u64::try_from(seconds).ok()?, nanos))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_tai64n_prefix() {
+        let line = LogLine::parse("@400000005f5e100a1dcd6500 started worker 3");
+        assert_eq!(line.timestamp, Some(1_600_000_000.5));
+        assert_eq!(line.message, "started worker 3");
+    }
+
+    #[test]
+    fn parses_human_readable_prefixes() {
+        for prefix in ["2020-09-13_12:26:40.50000", "2020-09-13T12:26:40.50000"] {
+            let line = LogLine::parse(&format!("{} listening", prefix));
+            assert_eq!(line.timestamp, Some(1_600_000_000.5), "{}", prefix);
+            assert_eq!(line.message, "listening");
+        }
+    }
+
+    #[test]
+    fn keeps_lines_without_timestamp() {
+        for text in ["plain message", "@nothex message", "", "@400000005f5e100a1dcd6500"] {
+            let line = LogLine::parse(text);
+            assert_eq!(line.timestamp, None, "{}", text);
+            assert_eq!(line.message, text);
+        }
+    }
+
+    #[test]
+    fn parses_time_bounds() {
+        assert_eq!(parse_time_bound("1600000000"), Some(1_600_000_000.0));
+        assert_eq!(parse_time_bound("2020-09-13T12:26:40Z"), Some(1_600_000_000.0));
+        assert_eq!(parse_time_bound("yesterday"), None);
+    }
+}
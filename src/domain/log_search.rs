@@ -0,0 +1,197 @@
+use std::collections::VecDeque;
+use regex::{Regex, RegexBuilder};
+use serde::Serialize;
+
+use crate::application::service_info::ServiceInfo;
+use crate::domain::log_line::LogLine;
+use crate::domain::service_logs::{self, TimeRange};
+use crate::error::AppError;
+
+/// A grep-like query over a service's log stream.
+#[derive(Debug)]
+pub struct LogSearch {
+    pattern: Regex,
+    invert: bool,
+    before: usize,
+    after: usize,
+    limit: usize,
+}
+
+/// A line emitted by a search: either a match or one of its context lines.
+#[derive(Serialize, Debug)]
+pub struct SearchLine {
+    #[serde(flatten)]
+    pub line: LogLine,
+    pub matched: bool,
+    /// `[start, end)` character offsets of the matches within `message`.
+    pub highlights: Vec<(usize, usize)>,
+    /// Lines were skipped between this line and the previous one, like grep's `--`.
+    pub gap: bool,
+    #[serde(skip)]
+    sequence: usize,
+}
+
+impl LogSearch {
+    /// Builds a search for `pattern`, taken literally unless `regex` is set.
+    pub fn new(
+        pattern: &str,
+        regex: bool,
+        ignore_case: bool,
+        invert: bool,
+        before: usize,
+        after: usize,
+        limit: usize,
+    ) -> Result<Self, AppError> {
+        let source = if regex { pattern.to_string() } else { regex::escape(pattern) };
+        let pattern = RegexBuilder::new(&source)
+            .case_insensitive(ignore_case)
+            .build()
+            .map_err(|e| AppError::InvalidQuery(format!("invalid pattern: {}", e)))?;
+
+        Ok(Self {
+            pattern,
+            invert,
+            before,
+            after,
+            limit,
+        })
+    }
+
+    fn highlights(&self, message: &str) -> Vec<(usize, usize)> {
+        if self.invert {
+            return Vec::new();
+        }
+
+        self.pattern
+            .find_iter(message)
+            .map(|m| {
+                let start = message[..m.start()].chars().count();
+                (start, start + m.as_str().chars().count())
+            })
+            .collect()
+    }
+
+    fn to_search_line(&self, line: LogLine, matched: bool, sequence: usize) -> SearchLine {
+        SearchLine {
+            highlights: if matched { self.highlights(&line.message) } else { Vec::new() },
+            line,
+            matched,
+            gap: false,
+            sequence,
+        }
+    }
+
+    /// Runs the search over `lines` given newest first, returning the newest `limit`
+    /// matches with their context in chronological order.
+    ///
+    /// Walking backwards, the lines just seen are the after-context of the next
+    /// match and the lines still to come are its before-context.
+    pub fn run(&self, lines: impl Iterator<Item = LogLine>) -> Vec<SearchLine> {
+        let mut output = Vec::new();
+        let mut unemitted: VecDeque<(usize, LogLine)> = VecDeque::with_capacity(self.after + 1);
+        let mut pending_before = 0;
+        let mut matches = 0;
+
+        for (sequence, line) in lines.enumerate() {
+            let matched = self.pattern.is_match(&line.message) != self.invert;
+
+            // A match beyond the limit ends the search rather than passing for context
+            // of the last one, as it would be reported without being a match
+            if matched && matches == self.limit {
+                break;
+            }
+
+            if matched {
+                matches += 1;
+                output.extend(unemitted.drain(..).map(|(sequence, line)| self.to_search_line(line, false, sequence)));
+                output.push(self.to_search_line(line, true, sequence));
+                pending_before = self.before;
+            } else if pending_before > 0 {
+                output.push(self.to_search_line(line, false, sequence));
+                pending_before -= 1;
+            } else if self.after > 0 {
+                unemitted.push_back((sequence, line));
+                if unemitted.len() > self.after {
+                    unemitted.pop_front();
+                }
+            }
+
+            if matches == self.limit && pending_before == 0 {
+                break;
+            }
+        }
+
+        output.reverse();
+        for index in 1..output.len() {
+            output[index].gap = output[index - 1].sequence != output[index].sequence + 1;
+        }
+
+        output
+    }
+}
+
+/// Searches `current` and the rotated archives of a service within `range`.
+pub fn search_service_logs(service_info: &ServiceInfo, search: &LogSearch, range: TimeRange) -> Result<Vec<SearchLine>, AppError> {
+    let lines = service_logs::service_log_lines(service_info, range)?;
+    Ok(search.run(lines))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use crate::domain::log_source::{LogTarget, Logger};
+
+    /// Runs `search` over `lines` given oldest first, as they appear in a file.
+    fn run(search: &LogSearch, lines: &[&str]) -> Vec<SearchLine> {
+        search.run(lines.iter().rev().map(|line| LogLine::parse(line)))
+    }
+
+    fn summary(output: &[SearchLine]) -> Vec<(&str, bool, bool)> {
+        output.iter().map(|line| (line.line.message.as_str(), line.matched, line.gap)).collect()
+    }
+
+    #[test]
+    fn overlapping_context_is_emitted_once() {
+        let search = LogSearch::new("m", false, false, false, 1, 1, 10).unwrap();
+        let output = run(&search, &["m1", "x", "m2", "y", "z"]);
+        assert_eq!(
+            summary(&output),
+            [("m1", true, false), ("x", false, false), ("m2", true, false), ("y", false, false)]
+        );
+        assert_eq!(output[0].highlights, [(0, 1)]);
+    }
+
+    #[test]
+    fn context_stops_at_a_match_beyond_the_limit() {
+        let search = LogSearch::new("m", false, false, false, 2, 0, 2).unwrap();
+        let output = run(&search, &["m1", "a", "m2", "b", "m3"]);
+        assert_eq!(
+            summary(&output),
+            [("a", false, false), ("m2", true, false), ("b", false, false), ("m3", true, false)]
+        );
+    }
+
+    #[test]
+    fn context_crosses_into_the_archive() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("@400000006500000000000000.s"), "m1\na\n").unwrap();
+        fs::write(dir.path().join("current"), "b\nm2\nc\nd\ne\nm3\n").unwrap();
+        let files = service_logs::log_files(&LogTarget::Directory { path: dir.path().to_path_buf(), logger: Logger::Svlogd }).unwrap();
+
+        let search = LogSearch::new("m", false, false, false, 2, 0, 10).unwrap();
+        let output = search.run(service_logs::file_lines(files, TimeRange::default()));
+        assert_eq!(
+            summary(&output),
+            [
+                ("m1", true, false),
+                ("a", false, false),
+                ("b", false, false),
+                ("m2", true, false),
+                ("d", false, true),
+                ("e", false, false),
+                ("m3", true, false),
+            ]
+        );
+    }
+}
@@ -0,0 +1,302 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use serde::Serialize;
+
+/// File in a service directory naming where its logs are, overriding detection.
+pub const LOG_OVERRIDE_FILE: &str = "runit-ui-log";
+
+/// The program writing a log directory.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Logger {
+    /// svlogd, whose directory `config` can be edited.
+    Svlogd,
+    /// s6-log, configured by its command line rather than a file in the directory.
+    S6Log,
+    /// Named by an override file, so the writing program isn't known.
+    Unknown,
+}
+
+/// Where a service's log output is written.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum LogTarget {
+    /// A logger directory holding `current` and rotated archives.
+    Directory { path: PathBuf, logger: Logger },
+    /// A plain file the output is appended to.
+    File { path: PathBuf },
+}
+
+impl LogTarget {
+    fn from_path(path: PathBuf) -> Self {
+        if path.is_dir() {
+            Self::Directory { path, logger: Logger::Unknown }
+        } else {
+            Self::File { path }
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        match self {
+            Self::Directory { path, .. } | Self::File { path } => path,
+        }
+    }
+
+    /// The file new lines are appended to.
+    pub fn live_file(&self) -> PathBuf {
+        match self {
+            Self::Directory { path, .. } => path.join("current"),
+            Self::File { path } => path.clone(),
+        }
+    }
+}
+
+/// Tokens that end a simple command in a shell script.
+const SHELL_SEPARATORS: [&str; 5] = [";", "|", "&&", "||", "&"];
+
+fn resolve(path: &str, cwd: &Path) -> PathBuf {
+    let path = cwd.join(path);
+    fs::canonicalize(&path).unwrap_or(path)
+}
+
+fn program_name(token: &str) -> &str {
+    token.rsplit('/').next().unwrap_or(token)
+}
+
+/// The file descriptor and target of a redirection token such as `>>file`, `2>file` or
+/// `>`, `file`; the second value tells whether the target was the next token.
+fn redirection<'a>(token: &'a str, next: Option<&'a str>) -> Option<(&'a str, &'a str, bool)> {
+    let operator_start = token.find(|c: char| !c.is_ascii_digit())?;
+    let (fd, operator) = token.split_at(operator_start);
+    let target = operator.strip_prefix(">>").or_else(|| operator.strip_prefix('>'))?;
+    if target.is_empty() {
+        Some((fd, next?, true))
+    } else {
+        Some((fd, target, false))
+    }
+}
+
+/// Splits a command into its words and the files its standard output or error is
+/// redirected to; descriptor duplications like `2>&1` and `/dev/null` name no file.
+fn split_redirections<'a>(tokens: &[&'a str]) -> (Vec<&'a str>, Vec<&'a str>) {
+    let mut words = Vec::new();
+    let mut targets = Vec::new();
+    let mut index = 0;
+    while index < tokens.len() {
+        match redirection(tokens[index], tokens.get(index + 1).copied()) {
+            Some((fd, target, separate)) => {
+                if matches!(fd, "" | "1" | "2") && !target.starts_with('&') && target != "/dev/null" {
+                    targets.push(target);
+                }
+                index += if separate { 2 } else { 1 };
+            }
+            None => {
+                words.push(tokens[index]);
+                index += 1;
+            }
+        }
+    }
+    (words, targets)
+}
+
+fn is_unresolvable(arg: &str) -> bool {
+    arg.contains('$') || arg.contains('`')
+}
+
+/// A logger directory operand; shell globs such as socklog's `main/*` are expanded, preferring
+/// the `main` directory socklog-conf sets up.
+fn logger_directory(operand: &str, cwd: &Path, logger: Logger) -> Option<LogTarget> {
+    if is_unresolvable(operand) {
+        return None;
+    }
+    if !operand.contains(['*', '?', '[']) {
+        return Some(LogTarget::Directory { path: resolve(operand, cwd), logger });
+    }
+
+    let pattern = cwd.join(operand);
+    let mut matches: Vec<PathBuf> = glob::glob(&pattern.to_string_lossy())
+        .ok()?
+        .flatten()
+        .filter(|path| path.is_dir())
+        .collect();
+    if matches.is_empty() {
+        return None;
+    }
+    matches.sort();
+    let main = matches.iter().position(|path| path.file_name().is_some_and(|name| name == "main"));
+    let path = matches.swap_remove(main.unwrap_or(0));
+    Some(LogTarget::Directory { path: fs::canonicalize(&path).unwrap_or(path), logger })
+}
+
+/// Finds the log location in a logger command line, such as a log service's `run`
+/// script line or a running logger's arguments. Relative paths are taken from `cwd`.
+pub fn logger_target(args: &[&str], cwd: &Path) -> Option<LogTarget> {
+    let command_end = |start: usize| {
+        args[start..]
+            .iter()
+            .position(|arg| SHELL_SEPARATORS.contains(arg) || arg.contains('>'))
+            .map_or(args.len(), |end| start + end)
+    };
+
+    for (index, arg) in args.iter().enumerate() {
+        let operands = &args[index + 1..command_end(index + 1)];
+        match program_name(arg) {
+            // svlogd [-tttv] [-r c] [-R abc] [-l len] [-b buflen] dir...
+            // With several directories, as socklog-conf sets up, `main` gets the general log
+            "svlogd" => {
+                let directories: Vec<&str> = operands.iter().copied().filter(|operand| !operand.starts_with('-')).collect();
+                let main = directories.iter().find(|dir| Path::new(dir).file_name().is_some_and(|name| name == "main"));
+                if let Some(dir) = main.or(directories.last()) {
+                    return logger_directory(dir, cwd, Logger::Svlogd);
+                }
+            }
+            // s6-log [-bpt] [-l linelimit] script, where log directories start with `/` or `.`
+            "s6-log" => {
+                if let Some(dir) = operands.iter().rfind(|operand| operand.starts_with('/') || operand.starts_with('.')) {
+                    return logger_directory(dir, cwd, Logger::S6Log);
+                }
+            }
+            "tee" => {
+                if let Some(file) = operands.iter().find(|operand| !operand.starts_with('-')) {
+                    return (!is_unresolvable(file)).then(|| LogTarget::File { path: resolve(file, cwd) });
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// The file a command's output is redirected to, such as `exec cat >>file`.
+fn redirection_target(command: &[&str], cwd: &Path) -> Option<LogTarget> {
+    let (_, targets) = split_redirections(command);
+    let target = targets.last()?;
+    (!is_unresolvable(target)).then(|| LogTarget::File { path: resolve(target, cwd) })
+}
+
+/// Finds the log location written by a `run` script. Only the final `exec`, which starts
+/// the service or its logger, is considered, along with earlier `exec` lines that merely
+/// redirect the script's own output; other redirections, such as writing a pidfile, don't
+/// carry the service's output and leave the location unresolved.
+pub fn script_target(script: &str, cwd: &Path) -> Option<LogTarget> {
+    let commands: Vec<Vec<&str>> = script
+        .lines()
+        .map(|line| {
+            line.split_whitespace()
+                .take_while(|token| !token.starts_with('#'))
+                .map(|token| token.trim_matches(|c| c == '"' || c == '\''))
+                .collect::<Vec<&str>>()
+        })
+        .filter(|tokens| tokens.first() == Some(&"exec"))
+        .map(|tokens| tokens[1..].to_vec())
+        .collect();
+    let starts_program = |command: &Vec<&str>| !split_redirections(command).0.is_empty();
+
+    let last = commands.iter().rposition(starts_program)?;
+    let command = &commands[last];
+    logger_target(command, cwd)
+        .or_else(|| redirection_target(command, cwd))
+        .or_else(|| {
+            commands[..last]
+                .iter()
+                .rev()
+                .filter(|command| !starts_program(command))
+                .find_map(|command| redirection_target(command, cwd))
+        })
+}
+
+/// Reads the override file of a service directory; relative paths are taken from the service directory.
+pub fn read_override(service_dir: &Path) -> Option<LogTarget> {
+    let content = fs::read_to_string(service_dir.join(LOG_OVERRIDE_FILE)).ok()?;
+    let path = content
+        .lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty() && !line.starts_with('#'))?;
+
+    Some(LogTarget::from_path(resolve(path, service_dir)))
+}
+
+/// Log location of a run script in `dir`, such as a log service's `log/run`.
+pub fn run_script_target(dir: &Path) -> Option<LogTarget> {
+    let script = fs::read_to_string(dir.join("run")).ok()?;
+    script_target(&script, dir)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn target(script: &str, cwd: &Path) -> Option<LogTarget> {
+        script_target(script, cwd)
+    }
+
+    fn svlogd(path: PathBuf) -> Option<LogTarget> {
+        Some(LogTarget::Directory { path, logger: Logger::Svlogd })
+    }
+
+    #[test]
+    fn svlogd_and_s6_log_directories() {
+        let cwd = Path::new("/nonexistent/sv/app/log");
+        assert_eq!(
+            target("#!/bin/sh\nexec svlogd -tt /var/log/app\n", cwd),
+            svlogd(PathBuf::from("/var/log/app"))
+        );
+        assert_eq!(
+            target("#!/bin/sh\nexec chpst -u log svlogd -tt ./main\n", cwd),
+            svlogd(cwd.join("./main"))
+        );
+        assert_eq!(
+            target("#!/bin/execlineb -P\nexec s6-log -b n20 s1000000 t /var/log/app\n", cwd),
+            Some(LogTarget::Directory { path: PathBuf::from("/var/log/app"), logger: Logger::S6Log })
+        );
+    }
+
+    #[test]
+    fn output_redirected_by_the_final_exec() {
+        let cwd = Path::new("/nonexistent/sv/app");
+        assert_eq!(
+            target("#!/bin/sh\nexec 2>&1\nexec app --foreground >> /var/log/app.log\n", cwd),
+            Some(LogTarget::File { path: PathBuf::from("/var/log/app.log") })
+        );
+        assert_eq!(
+            target("#!/bin/sh\nexec cat >>/var/log/app.log\n", cwd),
+            Some(LogTarget::File { path: PathBuf::from("/var/log/app.log") })
+        );
+        // The script's own output, redirected before the service is started
+        assert_eq!(
+            target("#!/bin/sh\nexec >>/var/log/app.log 2>&1\nexec app\n", cwd),
+            Some(LogTarget::File { path: PathBuf::from("/var/log/app.log") })
+        );
+    }
+
+    #[test]
+    fn other_redirections_are_not_log_targets() {
+        let cwd = Path::new("/nonexistent/sv/app");
+        assert_eq!(target("#!/bin/sh\nexec 2>&1\necho $$ > /run/app.pid\nexec app\n", cwd), None);
+        assert_eq!(target("#!/bin/sh\nexec app 2>&1 > /dev/null\n", cwd), None);
+        assert_eq!(target("#!/bin/sh\nexec app >> \"$LOGFILE\"\n", cwd), None);
+        assert_eq!(target("#!/bin/sh\napp > /var/log/app.log\n", cwd), None);
+        // Lines after the final exec never run
+        assert_eq!(target("#!/bin/sh\nexec app\necho done > /tmp/app.done\n", cwd), None);
+    }
+
+    #[test]
+    fn socklog_directories() {
+        let dir = tempfile::tempdir().unwrap();
+        for name in ["auth", "main", "kernel"] {
+            fs::create_dir_all(dir.path().join("main").join(name)).unwrap();
+        }
+        let cwd = fs::canonicalize(dir.path()).unwrap();
+
+        // As written by socklog-conf for the socklog-unix log service
+        assert_eq!(
+            target("#!/bin/sh\nexec chpst -ulog svlogd main/main main/auth main/kernel\n", &cwd),
+            svlogd(cwd.join("main/main"))
+        );
+        assert_eq!(
+            target("#!/bin/sh\nexec chpst -ulog svlogd -tt main/*\n", &cwd),
+            svlogd(cwd.join("main/main"))
+        );
+        assert_eq!(target("#!/bin/sh\nexec svlogd -tt missing/*\n", &cwd), None);
+    }
+}
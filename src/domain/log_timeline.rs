@@ -0,0 +1,206 @@
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BinaryHeap};
+use serde::Serialize;
+
+use crate::domain::log_line::LogLine;
+use crate::domain::service_name::ServiceName;
+use crate::error::AppError;
+
+/// A log line labelled with the service that wrote it.
+#[derive(Serialize, Debug)]
+pub struct TimelineLine {
+    pub service: String,
+    #[serde(flatten)]
+    pub line: LogLine,
+}
+
+/// One service's log lines, newest first.
+pub struct ServiceLines {
+    service: String,
+    lines: Box<dyn Iterator<Item = LogLine>>,
+    /// Lines without a timestamp sort with the newer line before them.
+    last_timestamp: f64,
+}
+
+impl ServiceLines {
+    pub fn new(service: String, lines: impl Iterator<Item = LogLine> + 'static) -> Self {
+        Self {
+            service,
+            lines: Box::new(lines),
+            last_timestamp: f64::INFINITY,
+        }
+    }
+
+    fn next_head(&mut self, stream: usize) -> Option<Head> {
+        let line = self.lines.next()?;
+        if let Some(timestamp) = line.timestamp {
+            self.last_timestamp = timestamp;
+        }
+
+        Some(Head {
+            sort_key: self.last_timestamp,
+            stream,
+            line,
+        })
+    }
+}
+
+/// The newest unread line of a stream, ordered by time for the merge heap.
+struct Head {
+    sort_key: f64,
+    stream: usize,
+    line: LogLine,
+}
+
+impl Ord for Head {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.sort_key
+            .total_cmp(&other.sort_key)
+            // Ties pop the later stream first, so they end up in stream order once reversed
+            .then_with(|| self.stream.cmp(&other.stream))
+    }
+}
+
+impl PartialOrd for Head {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Head {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Head {}
+
+/// Interleaves the newest `lines` lines of several services by timestamp,
+/// returned in chronological order.
+pub fn merge_service_lines(mut streams: Vec<ServiceLines>, lines: usize) -> Vec<TimelineLine> {
+    let mut heap: BinaryHeap<Head> = streams
+        .iter_mut()
+        .enumerate()
+        .filter_map(|(index, stream)| stream.next_head(index))
+        .collect();
+    let mut merged = Vec::new();
+
+    while merged.len() < lines {
+        let Some(head) = heap.pop() else {
+            break;
+        };
+
+        let stream = &mut streams[head.stream];
+        if let Some(next) = stream.next_head(head.stream) {
+            heap.push(next);
+        }
+
+        merged.push(TimelineLine {
+            service: stream.service.clone(),
+            line: head.line,
+        });
+    }
+
+    merged.reverse();
+    merged
+}
+
+/// The merged logs of several services, with the services whose logs couldn't be read.
+#[derive(Serialize, Debug)]
+pub struct Timeline {
+    pub logs: Vec<TimelineLine>,
+    /// Error message by service name.
+    pub errors: BTreeMap<String, String>,
+}
+
+/// Reads the logs of each of `services` with `read` and merges the newest `lines` of them.
+/// A service that can't be read is reported under `errors` instead of failing the timeline.
+pub fn service_timeline<I>(
+    services: &[ServiceName],
+    lines: usize,
+    read: impl Fn(&ServiceName) -> Result<I, AppError>,
+) -> Timeline
+where
+    I: Iterator<Item = LogLine> + 'static,
+{
+    let mut streams = Vec::new();
+    let mut errors = BTreeMap::new();
+
+    for service_name in services {
+        match read(service_name) {
+            Ok(lines) => streams.push(ServiceLines::new(service_name.to_string(), lines)),
+            Err(e) => {
+                errors.insert(service_name.to_string(), e.to_string());
+            }
+        }
+    }
+
+    Timeline {
+        logs: merge_service_lines(streams, lines),
+        errors,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line(timestamp: Option<f64>, message: &str) -> LogLine {
+        LogLine { timestamp, message: message.to_string() }
+    }
+
+    /// A stream from lines given oldest first, as they appear in a file.
+    fn stream(service: &str, lines: Vec<LogLine>) -> ServiceLines {
+        ServiceLines::new(service.to_string(), lines.into_iter().rev())
+    }
+
+    fn summary(merged: &[TimelineLine]) -> Vec<(&str, &str)> {
+        merged.iter().map(|line| (line.service.as_str(), line.line.message.as_str())).collect()
+    }
+
+    #[test]
+    fn interleaves_by_timestamp_and_keeps_the_newest() {
+        let streams = vec![
+            stream("web", vec![line(Some(1.0), "w1"), line(Some(4.0), "w4")]),
+            stream("db", vec![line(Some(2.0), "d2"), line(Some(3.0), "d3"), line(Some(5.0), "d5")]),
+        ];
+        let merged = merge_service_lines(streams, 4);
+        assert_eq!(summary(&merged), [("db", "d2"), ("db", "d3"), ("web", "w4"), ("db", "d5")]);
+    }
+
+    #[test]
+    fn equal_timestamps_keep_stream_order() {
+        let streams = vec![
+            stream("web", vec![line(Some(1.0), "w1")]),
+            stream("db", vec![line(Some(1.0), "d1")]),
+        ];
+        assert_eq!(summary(&merge_service_lines(streams, 10)), [("web", "w1"), ("db", "d1")]);
+    }
+
+    #[test]
+    fn lines_without_timestamp_stay_before_the_next_stamped_line() {
+        let streams = vec![
+            stream("web", vec![line(Some(1.0), "w1"), line(None, "trace"), line(Some(3.0), "w3")]),
+            stream("db", vec![line(Some(2.0), "d2")]),
+        ];
+        assert_eq!(
+            summary(&merge_service_lines(streams, 10)),
+            [("web", "w1"), ("db", "d2"), ("web", "trace"), ("web", "w3")]
+        );
+    }
+
+    #[test]
+    fn unreadable_services_are_reported_in_errors() {
+        let services = [ServiceName::parse("web").unwrap(), ServiceName::parse("db").unwrap()];
+        let timeline = service_timeline(&services, 10, |service_name| {
+            if service_name.to_string() == "db" {
+                return Err(AppError::NoLogService("db".to_string()));
+            }
+            Ok(vec![line(Some(1.0), "w1")].into_iter())
+        });
+
+        assert_eq!(summary(&timeline.logs), [("web", "w1")]);
+        assert_eq!(timeline.errors.keys().collect::<Vec<_>>(), ["db"]);
+        assert_eq!(timeline.errors["db"], AppError::NoLogService("db".to_string()).to_string());
+    }
+}
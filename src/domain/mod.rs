@@ -1,4 +1,13 @@
+pub mod log_download;
+pub mod log_line;
+pub mod log_search;
+pub mod log_source;
+pub mod log_timeline;
 pub mod network_ports;
 pub mod service;
+pub mod service_name;
 pub mod service_logs;
 pub mod service_tree;
+pub mod supervise_status;
+pub mod svlogd_config;
+pub mod tai64;
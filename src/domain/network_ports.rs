@@ -0,0 +1,206 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::net::{Ipv4Addr, Ipv6Addr};
+use serde::Serialize;
+
+use crate::domain::service_tree::{self, ProcessEntry};
+
+/// TCP state `LISTEN` in `/proc/net/tcp*`.
+const TCP_LISTEN: &str = "0A";
+/// UDP state `TCP_CLOSE` is used for unconnected, bound sockets in `/proc/net/udp*`.
+const UDP_UNCONNECTED: &str = "07";
+/// `__SO_ACCEPTCON` flag marking listening sockets in `/proc/net/unix`.
+const UNIX_ACCEPTCON: u32 = 0x0001_0000;
+/// Socket type `SOCK_DGRAM` in `/proc/net/unix`.
+const UNIX_DGRAM: &str = "0002";
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Protocol {
+    Tcp,
+    Tcp6,
+    Udp,
+    Udp6,
+    Unix,
+}
+
+impl Protocol {
+    fn proc_file(&self) -> &'static str {
+        match self {
+            Protocol::Tcp => "/proc/net/tcp",
+            Protocol::Tcp6 => "/proc/net/tcp6",
+            Protocol::Udp => "/proc/net/udp",
+            Protocol::Udp6 => "/proc/net/udp6",
+            Protocol::Unix => "/proc/net/unix",
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct ListeningSocket {
+    pub protocol: Protocol,
+    /// `ip:port` for inet sockets, the bound path (`@name` when abstract) for unix sockets.
+    pub address: String,
+    pub port: Option<u16>,
+    pub pid: u32,
+}
+
+/// Snapshot of the listening sockets known to the kernel, keyed by socket inode.
+pub struct SocketTable {
+    sockets: HashMap<u64, (Protocol, String, Option<u16>)>,
+}
+
+impl SocketTable {
+    pub fn read() -> Self {
+        let mut sockets = HashMap::new();
+
+        for protocol in [Protocol::Tcp, Protocol::Tcp6, Protocol::Udp, Protocol::Udp6] {
+            let listen_state = match protocol {
+                Protocol::Tcp | Protocol::Tcp6 => TCP_LISTEN,
+                _ => UDP_UNCONNECTED,
+            };
+            for line in fs::read_to_string(protocol.proc_file()).unwrap_or_default().lines().skip(1) {
+                let fields: Vec<&str> = line.split_whitespace().collect();
+                if fields.len() < 10 || fields[3] != listen_state {
+                    continue;
+                }
+                let (Some((address, port)), Ok(inode)) = (parse_inet_address(fields[1]), fields[9].parse()) else {
+                    continue;
+                };
+                sockets.insert(inode, (protocol, address, Some(port)));
+            }
+        }
+
+        for line in fs::read_to_string(Protocol::Unix.proc_file()).unwrap_or_default().lines().skip(1) {
+            let Some((fields, path)) = split_unix_line(line) else {
+                continue;
+            };
+            let flags = u32::from_str_radix(fields[3], 16).unwrap_or(0);
+            if flags & UNIX_ACCEPTCON == 0 && fields[4] != UNIX_DGRAM {
+                continue;
+            }
+            if let Ok(inode) = fields[6].parse() {
+                sockets.insert(inode, (Protocol::Unix, path.to_string(), None));
+            }
+        }
+
+        Self { sockets }
+    }
+
+    /// Listening sockets held open by any of `pids`, each reported once.
+    pub fn listening(&self, pids: &[u32]) -> Vec<ListeningSocket> {
+        let mut seen = HashSet::new();
+        let mut result = Vec::new();
+
+        for &pid in pids {
+            for inode in socket_inodes(pid) {
+                if !seen.insert(inode) {
+                    continue;
+                }
+                if let Some((protocol, address, port)) = self.sockets.get(&inode) {
+                    result.push(ListeningSocket {
+                        protocol: *protocol,
+                        address: address.clone(),
+                        port: *port,
+                        pid,
+                    });
+                }
+            }
+        }
+
+        result
+    }
+}
+
+/// Socket inodes from the `socket:[<inode>]` links in `/proc/<pid>/fd`.
+fn socket_inodes(pid: u32) -> Vec<u64> {
+    fs::read_dir(format!("/proc/{}/fd", pid))
+        .map(|entries| {
+            entries
+                .flatten()
+                .filter_map(|entry| {
+                    let target = fs::read_link(entry.path()).ok()?;
+                    target.to_str()?
+                        .strip_prefix("socket:[")?
+                        .strip_suffix(']')?
+                        .parse()
+                        .ok()
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Splits a `/proc/net/unix` line into its seven fixed fields and the bound path, which is
+/// the rest of the line after the inode and may contain spaces. Unbound sockets have no path.
+fn split_unix_line(line: &str) -> Option<([&str; 7], &str)> {
+    let mut fields = [""; 7];
+    let mut rest = line;
+    for field in &mut fields {
+        let trimmed = rest.trim_start();
+        let end = trimmed.find(' ')?;
+        (*field, rest) = trimmed.split_at(end);
+    }
+    // The inode is followed by a single space, anything after it belongs to the path
+    let path = rest.strip_prefix(' ').filter(|path| !path.is_empty())?;
+    Some((fields, path))
+}
+
+/// Parses `0100007F:1F90` (IPv4) or the 32 hex digit IPv6 form. The kernel prints
+/// addresses as host-endian 32-bit words, so each word is byte-swapped.
+fn parse_inet_address(field: &str) -> Option<(String, u16)> {
+    let (address, port) = field.split_once(':')?;
+    let port = u16::from_str_radix(port, 16).ok()?;
+
+    let mut octets = Vec::with_capacity(16);
+    for chunk in address.as_bytes().chunks(8) {
+        let word = u32::from_str_radix(std::str::from_utf8(chunk).ok()?, 16).ok()?;
+        octets.extend_from_slice(&word.to_ne_bytes());
+    }
+
+    let address = match octets.len() {
+        4 => format!("{}:{}", Ipv4Addr::new(octets[0], octets[1], octets[2], octets[3]), port),
+        16 => {
+            let octets: [u8; 16] = octets.try_into().ok()?;
+            format!("[{}]:{}", Ipv6Addr::from(octets), port)
+        }
+        _ => return None,
+    };
+
+    Some((address, port))
+}
+
+/// Listening sockets owned by the process `pid` and all of its descendants.
+pub fn service_ports(pid: u32, processes: &[ProcessEntry], sockets: &SocketTable) -> Vec<ListeningSocket> {
+    sockets.listening(&service_tree::descendant_pids(pid, processes))
+}
+
+/// Listening sockets owned by the process `pid` and all of its descendants, reading only that
+/// process tree. The socket tables aren't read at all when the tree holds no sockets.
+pub fn tree_ports(pid: u32) -> Vec<ListeningSocket> {
+    let pids = service_tree::tree_pids(pid);
+    if pids.iter().all(|&pid| socket_inodes(pid).is_empty()) {
+        return Vec::new();
+    }
+    SocketTable::read().listening(&pids)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unix_line_keeps_spaces_in_path() {
+        let line = "0000000000000000: 00000002 00000000 00010000 0001 01  4242 /run/my app/ctl sock";
+        let (fields, path) = split_unix_line(line).unwrap();
+        assert_eq!(fields[3], "00010000");
+        assert_eq!(fields[6], "4242");
+        assert_eq!(path, "/run/my app/ctl sock");
+    }
+
+    #[test]
+    fn unix_line_without_path_is_skipped() {
+        let line = "0000000000000000: 00000003 00000000 00000000 0001 03 17052";
+        assert!(split_unix_line(line).is_none());
+    }
+}
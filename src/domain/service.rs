@@ -1,19 +1,149 @@
+use crate::application::blocking::run_blocking;
 use crate::application::service_info::ServiceInfo;
+use crate::config::app_config::AppConfig;
+use crate::config::layout::RunitLayout;
+use crate::domain::network_ports::SocketTable;
+use crate::domain::service_name::ServiceName;
+use crate::domain::service_tree;
+use crate::domain::supervise_status;
+use std::collections::BTreeSet;
 use std::fs;
+use std::path::{Path, PathBuf};
+use futures_util::{stream, StreamExt};
 use log::info;
+use crate::error::AppError;
+use serde::Serialize;
 
-pub fn fetch_service_list(services_dir: &str) -> Vec<ServiceInfo> {
-    let mut service_list = Vec::new();
+/// How a service is registered with runit across the definition and active directories.
+#[derive(Serialize, Debug, Default, Clone)]
+pub struct Registration {
+    /// `<services_dir>/<name>` is a directory.
+    pub defined: bool,
+    /// `<active_dir>/<name>` exists, so runsvdir picks the service up.
+    pub enabled: bool,
+    /// A runsv process is supervising the service.
+    pub supervised: bool,
+    /// `<active_dir>/<name>` is a symlink whose target does not exist.
+    pub broken_link: bool,
+    /// `<active_dir>/<name>` resolves to a directory outside `<services_dir>`.
+    pub link_outside: bool,
+    /// Directory holding the service's `supervise` state: the active link when it resolves,
+    /// the definition otherwise.
+    #[serde(skip)]
+    pub directory: PathBuf,
+}
 
-    if let Ok(entries) = fs::read_dir(services_dir) {
-        for entry in entries.flatten() {
-            let service_name = entry.file_name().to_string_lossy().into_owned();
-            info!("Service found: {}", service_name);
-            if let Ok(service_info) = ServiceInfo::get_status(&service_name) {
-                service_list.push(service_info);
-            }
+impl Registration {
+    /// Inspects both directories for `name`, or returns `None` if neither knows the service.
+    pub fn inspect(layout: &RunitLayout, name: &ServiceName) -> Result<Option<Self>, AppError> {
+        let definition = name.definition_dir(layout)?;
+        let active = name.active_link(layout);
+
+        let defined = definition.is_dir();
+        let active_metadata = fs::symlink_metadata(&active).ok();
+        if !defined && active_metadata.is_none() {
+            return Ok(None);
         }
+
+        let enabled = active_metadata.is_some();
+        let is_link = active_metadata.is_some_and(|metadata| metadata.file_type().is_symlink());
+        let resolved = active.canonicalize().ok();
+        let broken_link = is_link && resolved.is_none();
+        let link_outside = match (&resolved, Path::new(&layout.services_dir).canonicalize()) {
+            (Some(resolved), Ok(services_dir)) => is_link && !resolved.starts_with(services_dir),
+            _ => false,
+        };
+
+        let directory = if enabled && !broken_link { active } else { definition };
+
+        Ok(Some(Self {
+            defined,
+            enabled,
+            supervised: supervise_status::is_supervised(&directory),
+            broken_link,
+            link_outside,
+            directory,
+        }))
     }
+}
+
+fn entry_names(dir: &str) -> Result<Vec<String>, AppError> {
+    let entries = fs::read_dir(dir).map_err(|e| AppError::io(format!("Failed to list {}", dir), e))?;
+    let mut names = Vec::new();
+    for entry in entries {
+        let entry = entry.map_err(|e| AppError::io(format!("Failed to list {}", dir), e))?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if !name.starts_with('.') {
+            names.push(name);
+        }
+    }
+    Ok(names)
+}
 
-    service_list
+/// Names of all services in the definition and active directories. Fails when either
+/// directory can't be read, rather than reporting a partial (or empty) set of services.
+pub fn service_names(layout: &RunitLayout) -> Result<BTreeSet<ServiceName>, AppError> {
+    // Services may be defined but not enabled, or linked from elsewhere without a definition
+    Ok(entry_names(&layout.services_dir)?
+        .into_iter()
+        .chain(entry_names(&layout.active_dir)?)
+        .filter_map(|name| ServiceName::parse(&name).ok())
+        .collect())
+}
+
+/// Queries the status of every service, `status_concurrency` at a time. Fails only when the
+/// services can't be listed; a service whose status can't be read in time gets its own error.
+pub async fn fetch_service_list(
+    config: &AppConfig,
+) -> Result<Vec<(ServiceName, Result<ServiceInfo, AppError>)>, AppError> {
+    let (names, processes, sockets) = {
+        let layout = config.layout.clone();
+        run_blocking("listing services", config.status_timeout(), move || {
+            Ok((service_names(&layout)?, service_tree::list_processes(), SocketTable::read()))
+        })
+        .await?
+    };
+
+    let list = stream::iter(names)
+        .map(|service_name| async {
+            info!("Service found: {}", service_name);
+            let result = ServiceInfo::fetch_status(config, service_name.clone()).await;
+            (service_name, result)
+        })
+        .buffered(config.status_concurrency)
+        .map(|(service_name, result)| {
+            let result = result.map(|mut service_info| {
+                service_info.attach_ports(&processes, &sockets);
+                service_info
+            });
+            (service_name, result)
+        })
+        .collect()
+        .await;
+    Ok(list)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unreadable_service_directory_is_an_error_not_an_empty_list() {
+        let root = tempfile::tempdir().unwrap();
+        let services_dir = root.path().join("sv");
+        let active_dir = root.path().join("service");
+        fs::create_dir_all(services_dir.join("nginx")).unwrap();
+        fs::create_dir_all(active_dir.join(".hidden")).unwrap();
+        let mut layout = RunitLayout {
+            services_dir: services_dir.display().to_string(),
+            active_dir: active_dir.display().to_string(),
+            bin_dir: root.path().display().to_string(),
+        };
+
+        let names = service_names(&layout).unwrap();
+        assert_eq!(names.iter().map(|name| name.to_string()).collect::<Vec<_>>(), ["nginx"]);
+
+        layout.active_dir = root.path().join("missing").display().to_string();
+        assert!(service_names(&layout).is_err());
+    }
 }
@@ -1,28 +1,795 @@
-use std::io::BufReader;
-use std::fs::File;
-use log::info;
-use rev_lines::RevLines;
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom};
+use std::fs::{self, File};
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+use flate2::read::GzDecoder;
+use log::{info, warn};
+use serde::{Serialize, Serializer};
 
 use crate::application::service_info::ServiceInfo;
+use crate::domain::log_line::LogLine;
+use crate::domain::log_source::{LogTarget, Logger};
+use crate::domain::tai64;
+use crate::error::AppError;
 
-fn read_last_lines(file_path: &str, num_lines: usize) -> std::io::Result<String> {
-    let file = File::open(file_path)?;
-    let reader = BufReader::new(file);
-    let rev_lines = RevLines::new(reader);
+/// Magic bytes starting a gzip stream, used to detect archives compressed by an svlogd processor.
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
 
-    // rev_lines yields lines from the end. Take the desired number of lines.
-    let mut collected: Vec<String> = rev_lines.take(num_lines)
-        .filter_map(Result::ok)
+/// Resolves where the logs of a service are written.
+pub fn log_target(service_info: &ServiceInfo) -> Result<&LogTarget, AppError> {
+    service_info.log_target.as_ref().ok_or_else(|| match &service_info.log {
+        None => AppError::NoLogService(service_info.name.clone()),
+        Some(log) => AppError::LogNotReadable {
+            service: service_info.name.clone(),
+            reason: format!("log service is {}, log location unknown", log.status),
+        },
+    })
+}
+
+/// The svlogd directory of a service, which holds its `config`. A directory named by an
+/// override file is taken to be one, as its logger isn't known.
+pub fn svlogd_directory(service_info: &ServiceInfo) -> Result<&Path, AppError> {
+    match log_target(service_info)? {
+        LogTarget::Directory { path, logger: Logger::Svlogd | Logger::Unknown } => Ok(path),
+        LogTarget::Directory { path, logger: Logger::S6Log } => Err(AppError::LogNotReadable {
+            service: service_info.name.clone(),
+            reason: format!("{} is written by s6-log, which has no svlogd config", path.display()),
+        }),
+        LogTarget::File { path } => Err(AppError::LogNotReadable {
+            service: service_info.name.clone(),
+            reason: format!("logs go to the plain file {}, not an svlogd directory", path.display()),
+        }),
+    }
+}
+
+/// The svlogd directory of a service whose `config` may be written, which takes svlogd to have
+/// been found writing to it, so no other logger's files are replaced.
+pub fn editable_svlogd_directory(service_info: &ServiceInfo) -> Result<&Path, AppError> {
+    let log_dir = svlogd_directory(service_info)?;
+    match log_target(service_info)? {
+        LogTarget::Directory { logger: Logger::Svlogd, .. } => Ok(log_dir),
+        _ => Err(AppError::InvalidAction(format!(
+            "the logger of {} wasn't identified as svlogd, so its log config can't be edited",
+            service_info.name
+        ))),
+    }
+}
+
+/// Time bounds in seconds since the UNIX epoch, both inclusive.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct TimeRange {
+    pub since: Option<f64>,
+    pub until: Option<f64>,
+}
+
+impl TimeRange {
+    /// Lines without a timestamp cannot be placed and are always kept.
+    pub fn contains(&self, timestamp: Option<f64>) -> bool {
+        let Some(timestamp) = timestamp else {
+            return true;
+        };
+        self.since.is_none_or(|since| timestamp >= since) && self.until.is_none_or(|until| timestamp <= until)
+    }
+}
+
+/// A position in a service's log stream: a file, identified by inode so it survives
+/// svlogd renaming `current` to an archive, and a byte offset within it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LogCursor {
+    pub inode: u64,
+    /// Rotation time, in whole seconds, of the archive the cursor points into; `None` for
+    /// the live file. Archives keep it for good, so an inode reused by a later file of the
+    /// directory doesn't match.
+    pub rotated_at: Option<u64>,
+    pub offset: u64,
+}
+
+impl LogCursor {
+    fn new(file: &LogFile, offset: u64) -> Self {
+        Self { inode: file.inode, rotated_at: file.rotation_label(), offset }
+    }
+
+    /// The opaque form handed to clients: `<inode>.<offset>`, followed by `.<rotated_at>`
+    /// for archives, all in hex.
+    pub fn encode(&self) -> String {
+        match self.rotated_at {
+            Some(rotated_at) => format!("{:x}.{:x}.{:x}", self.inode, self.offset, rotated_at),
+            None => format!("{:x}.{:x}", self.inode, self.offset),
+        }
+    }
+
+    pub fn decode(value: &str) -> Option<Self> {
+        let mut parts = value.split('.');
+        let inode = u64::from_str_radix(parts.next()?, 16).ok()?;
+        let offset = u64::from_str_radix(parts.next()?, 16).ok()?;
+        let rotated_at = match parts.next() {
+            Some(rotated_at) => Some(u64::from_str_radix(rotated_at, 16).ok()?),
+            None => None,
+        };
+        if parts.next().is_some() {
+            return None;
+        }
+        Some(Self { inode, rotated_at, offset })
+    }
+
+    /// Whether the cursor points into `file`. A cursor taken in the live file follows its
+    /// inode into the archive it is renamed to; one taken in an archive must match that
+    /// archive's rotation time as well. The offset must still fall on a line boundary.
+    fn points_into(&self, file: &LogFile) -> bool {
+        file.inode == self.inode
+            && self.rotated_at.is_none_or(|rotated_at| file.rotation_label() == Some(rotated_at))
+            && at_line_boundary(&file.path, self.offset).unwrap_or(false)
+    }
+}
+
+impl Serialize for LogCursor {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.encode())
+    }
+}
+
+/// One file of an svlogd directory.
+#[derive(Debug, Clone)]
+pub struct LogFile {
+    pub path: PathBuf,
+    pub inode: u64,
+    /// When svlogd rotated the file, taken from its `@<tai64n>` name; `None` for `current`.
+    pub rotated_at: Option<f64>,
+}
+
+impl LogFile {
+    fn rotation_label(&self) -> Option<u64> {
+        self.rotated_at.map(|rotated_at| rotated_at as u64)
+    }
+}
+
+/// Lists the files of a log target oldest first, ending with the one being written.
+pub fn log_files(target: &LogTarget) -> io::Result<Vec<LogFile>> {
+    match target {
+        LogTarget::Directory { path, .. } => directory_log_files(path),
+        LogTarget::File { path } => plain_log_files(path),
+    }
+}
+
+/// Lists the archives (`@<tai64n>.s`, `@<tai64n>.u`, optionally compressed) of an
+/// svlogd directory oldest first, followed by `current`.
+fn directory_log_files(log_dir: &Path) -> io::Result<Vec<LogFile>> {
+    let mut files: Vec<LogFile> = fs::read_dir(log_dir)?
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name().into_string().ok()?;
+            let label = name.get(..25)?;
+            let suffix = &name[25..];
+            if !(suffix.starts_with(".s") || suffix.starts_with(".u")) {
+                return None;
+            }
+            let rotated_at = tai64::parse_tai64n_label(label)?
+                .duration_since(UNIX_EPOCH)
+                .ok()?
+                .as_secs_f64();
+            let inode = entry.metadata().ok()?.ino();
+            Some(LogFile { path: entry.path(), inode, rotated_at: Some(rotated_at) })
+        })
         .collect();
-    collected.reverse();
+    // Fixed width hex TAI64N labels sort chronologically
+    files.sort_by(|a, b| a.path.file_name().cmp(&b.path.file_name()));
+
+    let current = log_dir.join("current");
+    if let Ok(metadata) = fs::metadata(&current) {
+        files.push(LogFile { path: current, inode: metadata.ino(), rotated_at: None });
+    }
+
+    Ok(files)
+}
+
+/// Lists logrotate-style siblings of a plain log file (`<file>.1`, `<file>.2.gz`, ...)
+/// oldest first, followed by the file itself. Rotation times are taken from mtimes.
+fn plain_log_files(path: &Path) -> io::Result<Vec<LogFile>> {
+    let parent = path.parent().unwrap_or(Path::new("/"));
+    let prefix = format!("{}.", path.file_name().unwrap_or_default().to_string_lossy());
+
+    let mut rotated: Vec<(u32, LogFile)> = fs::read_dir(parent)?
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name().into_string().ok()?;
+            let suffix = name.strip_prefix(&prefix)?;
+            let generation: u32 = suffix.strip_suffix(".gz").unwrap_or(suffix).parse().ok()?;
+            let metadata = entry.metadata().ok()?;
+            let rotated_at = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?.as_secs_f64();
+            Some((generation, LogFile { path: entry.path(), inode: metadata.ino(), rotated_at: Some(rotated_at) }))
+        })
+        .collect();
+    // Higher generations are older
+    rotated.sort_by_key(|(generation, _)| std::cmp::Reverse(*generation));
+
+    let mut files: Vec<LogFile> = rotated.into_iter().map(|(_, file)| file).collect();
+    if let Ok(metadata) = fs::metadata(path) {
+        files.push(LogFile { path: path.to_path_buf(), inode: metadata.ino(), rotated_at: None });
+    }
+
+    Ok(files)
+}
+
+trait ReadSeek: Read + Seek {}
+
+impl<T: Read + Seek> ReadSeek for T {}
+
+/// Opens a log file, transparently decompressing gzip archives into memory.
+fn open_log_file(path: &Path) -> io::Result<Box<dyn ReadSeek>> {
+    let mut file = File::open(path)?;
+    let mut magic = [0u8; 2];
+    let is_gzip = file.read_exact(&mut magic).is_ok() && magic == GZIP_MAGIC;
+    file.seek(SeekFrom::Start(0))?;
+
+    if is_gzip {
+        let mut content = Vec::new();
+        GzDecoder::new(file).read_to_end(&mut content)?;
+        Ok(Box::new(io::Cursor::new(content)))
+    } else {
+        Ok(Box::new(file))
+    }
+}
+
+/// Whether `offset` is the start of a line of the file at `path`.
+fn at_line_boundary(path: &Path, offset: u64) -> io::Result<bool> {
+    if offset == 0 {
+        return Ok(true);
+    }
+    let mut reader = open_log_file(path)?;
+    if reader.seek(SeekFrom::End(0))? < offset {
+        return Ok(false);
+    }
+    reader.seek(SeekFrom::Start(offset - 1))?;
+    let mut byte = [0u8; 1];
+    reader.read_exact(&mut byte)?;
+    Ok(byte[0] == b'\n')
+}
+
+/// Offset just past the last complete line of the file at `path`.
+fn last_line_end(path: &Path) -> io::Result<u64> {
+    let lines = ReverseLines::new(open_log_file(path)?, None)?;
+    Ok(lines.position + lines.buffer.len() as u64)
+}
+
+/// A complete line of a log file and the byte range it occupies, newline included.
+struct RawLine {
+    start: u64,
+    end: u64,
+    text: String,
+}
+
+impl RawLine {
+    fn new(start: u64, bytes: &[u8]) -> Self {
+        Self {
+            start,
+            end: start + bytes.len() as u64,
+            text: String::from_utf8_lossy(bytes.strip_suffix(b"\n").unwrap_or(bytes)).into_owned(),
+        }
+    }
+}
+
+/// How much of a file is read at a time when walking it backwards.
+const REVERSE_READ_CHUNK: u64 = 64 * 1024;
+
+/// Reads the complete lines of a file backwards from an offset.
+struct ReverseLines {
+    reader: Box<dyn ReadSeek>,
+    /// File offset of the first byte in `buffer`.
+    position: u64,
+    /// Unread bytes, always ending on a line boundary.
+    buffer: Vec<u8>,
+}
+
+impl ReverseLines {
+    /// Starts at `end`, or at the end of the file; a trailing line still being written is skipped.
+    fn new(mut reader: Box<dyn ReadSeek>, end: Option<u64>) -> io::Result<Self> {
+        let len = reader.seek(SeekFrom::End(0))?;
+        let mut lines = Self {
+            reader,
+            position: end.map_or(len, |end| end.min(len)),
+            buffer: Vec::new(),
+        };
+
+        loop {
+            if let Some(index) = lines.buffer.iter().rposition(|&b| b == b'\n') {
+                lines.buffer.truncate(index + 1);
+                break;
+            }
+            if lines.position == 0 {
+                lines.buffer.clear();
+                break;
+            }
+            lines.read_chunk()?;
+        }
+
+        Ok(lines)
+    }
+
+    fn read_chunk(&mut self) -> io::Result<()> {
+        let size = REVERSE_READ_CHUNK.min(self.position);
+        self.position -= size;
+        self.reader.seek(SeekFrom::Start(self.position))?;
+
+        let mut chunk = vec![0; size as usize];
+        self.reader.read_exact(&mut chunk)?;
+        chunk.append(&mut self.buffer);
+        self.buffer = chunk;
+        Ok(())
+    }
+
+    fn next_line(&mut self) -> io::Result<Option<RawLine>> {
+        loop {
+            // The last line starts after the newline preceding its own
+            let search_end = self.buffer.len().saturating_sub(1);
+            if let Some(index) = self.buffer[..search_end].iter().rposition(|&b| b == b'\n') {
+                let bytes = self.buffer.split_off(index + 1);
+                return Ok(Some(RawLine::new(self.position + index as u64 + 1, &bytes)));
+            }
+            if self.position == 0 {
+                if self.buffer.is_empty() {
+                    return Ok(None);
+                }
+                let bytes = std::mem::take(&mut self.buffer);
+                return Ok(Some(RawLine::new(0, &bytes)));
+            }
+            self.read_chunk()?;
+        }
+    }
+}
+
+impl Iterator for ReverseLines {
+    type Item = RawLine;
+
+    fn next(&mut self) -> Option<RawLine> {
+        self.next_line().unwrap_or_else(|e| {
+            warn!("Failed to read log file: {}", e);
+            None
+        })
+    }
+}
+
+/// Reads the complete lines of a file forwards from an offset.
+struct ForwardLines {
+    reader: BufReader<Box<dyn ReadSeek>>,
+    position: u64,
+}
+
+impl ForwardLines {
+    fn new(mut reader: Box<dyn ReadSeek>, start: u64) -> io::Result<Self> {
+        reader.seek(SeekFrom::Start(start))?;
+        Ok(Self {
+            reader: BufReader::new(reader),
+            position: start,
+        })
+    }
+
+    fn next_line(&mut self) -> io::Result<Option<RawLine>> {
+        let mut bytes = Vec::new();
+        self.reader.read_until(b'\n', &mut bytes)?;
+        // End of file, or a line still being written
+        if !bytes.ends_with(b"\n") {
+            return Ok(None);
+        }
+
+        let line = RawLine::new(self.position, &bytes);
+        self.position = line.end;
+        Ok(Some(line))
+    }
+}
+
+impl Iterator for ForwardLines {
+    type Item = RawLine;
 
-    Ok(collected.join("\n"))
+    fn next(&mut self) -> Option<RawLine> {
+        self.next_line().unwrap_or_else(|e| {
+            warn!("Failed to read log file: {}", e);
+            None
+        })
+    }
 }
 
-pub fn service_logs(service_info: ServiceInfo, lines: usize) -> std::io::Result<String> {
-    let log_path = service_info.log.unwrap().log_directory().unwrap();
-    let current_log_path = format!("{}/current", log_path);
+/// A parsed log line with the cursors just before and just after it.
+struct PositionedLine {
+    line: LogLine,
+    start: LogCursor,
+    end: LogCursor,
+}
+
+impl PositionedLine {
+    fn new(file: &LogFile, raw: RawLine) -> Self {
+        Self {
+            line: LogLine::parse(&raw.text),
+            start: LogCursor::new(file, raw.start),
+            end: LogCursor::new(file, raw.end),
+        }
+    }
+}
+
+/// Lines of `files` within `range` newest first, starting at `end` in the last file.
+fn lines_backward(files: Vec<LogFile>, end: Option<u64>, range: TimeRange) -> impl Iterator<Item = PositionedLine> {
+    files
+        .into_iter()
+        .rev()
+        .enumerate()
+        // Everything in an archive was written before it was rotated
+        .take_while(move |(_, file)| match (file.rotated_at, range.since) {
+            (Some(rotated_at), Some(since)) => rotated_at >= since,
+            _ => true,
+        })
+        .flat_map(move |(index, file)| {
+            let end = if index == 0 { end } else { None };
+            let lines: Box<dyn Iterator<Item = PositionedLine>> =
+                match open_log_file(&file.path).and_then(|reader| ReverseLines::new(reader, end)) {
+                    Ok(lines) => Box::new(lines.map(move |raw| PositionedLine::new(&file, raw))),
+                    Err(e) => {
+                        warn!("Failed to read {}: {}", file.path.display(), e);
+                        Box::new(std::iter::empty())
+                    }
+                };
+            lines
+        })
+        .filter(move |line| range.contains(line.line.timestamp))
+}
+
+/// Lines of `files` within `range` oldest first, starting at `start` in the first file.
+fn lines_forward(files: Vec<LogFile>, start: u64, range: TimeRange) -> impl Iterator<Item = PositionedLine> {
+    files
+        .into_iter()
+        .enumerate()
+        .flat_map(move |(index, file)| {
+            let start = if index == 0 { start } else { 0 };
+            let lines: Box<dyn Iterator<Item = PositionedLine>> =
+                match open_log_file(&file.path).and_then(|reader| ForwardLines::new(reader, start)) {
+                    Ok(lines) => Box::new(lines.map(move |raw| PositionedLine::new(&file, raw))),
+                    Err(e) => {
+                        warn!("Failed to read {}: {}", file.path.display(), e);
+                        Box::new(std::iter::empty())
+                    }
+                };
+            lines
+        })
+        .filter(move |line| range.contains(line.line.timestamp))
+}
+
+/// Complete lines of a log file oldest first, decompressing gzip archives.
+pub fn read_lines(path: &Path) -> io::Result<impl Iterator<Item = String>> {
+    Ok(ForwardLines::new(open_log_file(path)?, 0)?.map(|raw| raw.text))
+}
+
+/// The svlogd files of a service, oldest first.
+pub fn service_log_files(service_info: &ServiceInfo) -> Result<Vec<LogFile>, AppError> {
+    let target = log_target(service_info)?;
+    let files = log_files(target).map_err(|e| match e.kind() {
+        io::ErrorKind::NotFound => AppError::LogNotReadable {
+            service: service_info.name.clone(),
+            reason: format!("{} does not exist", target.path().display()),
+        },
+        _ => AppError::io(format!("Failed to list {}", target.path().display()), e),
+    })?;
+    info!("Reading {} log files of {}", files.len(), target.path().display());
+    Ok(files)
+}
+
+/// All log lines of a service within `range`, newest first, across `current` and
+/// the rotated archives. Files are opened lazily, so callers only pay for what they consume.
+pub fn service_log_lines(service_info: &ServiceInfo, range: TimeRange) -> Result<impl Iterator<Item = LogLine>, AppError> {
+    Ok(file_lines(service_log_files(service_info)?, range))
+}
+
+/// Log lines of `files` within `range`, newest first.
+pub fn file_lines(files: Vec<LogFile>, range: TimeRange) -> impl Iterator<Item = LogLine> {
+    lines_backward(files, None, range).map(|line| line.line)
+}
+
+/// Where a page of log lines is taken from.
+#[derive(Debug, Clone, Copy)]
+pub enum PageAnchor {
+    /// The newest lines.
+    Tail,
+    /// The lines just older than the cursor.
+    Before(LogCursor),
+    /// The lines just newer than the cursor.
+    After(LogCursor),
+}
+
+/// A page of log lines in chronological order, with the cursors to continue from.
+#[derive(Serialize, Debug)]
+pub struct LogPage {
+    pub logs: Vec<LogLine>,
+    /// Pass as `before` to fetch older lines.
+    pub before: Option<LogCursor>,
+    /// Pass as `after` to fetch lines written since.
+    pub after: Option<LogCursor>,
+}
+
+/// Index of the file a cursor points into, which may have been rotated since.
+fn locate_cursor(service: &str, files: &[LogFile], cursor: LogCursor) -> Result<usize, AppError> {
+    files
+        .iter()
+        .position(|file| cursor.points_into(file))
+        .ok_or_else(|| AppError::InvalidQuery(format!("cursor no longer matches a log file of {}", service)))
+}
+
+/// Reads up to `lines` lines within `range` next to `anchor`.
+pub fn service_log_page(service_info: &ServiceInfo, lines: usize, range: TimeRange, anchor: PageAnchor) -> Result<LogPage, AppError> {
+    let files = service_log_files(service_info)?;
+    read_page(&service_info.name, files, lines, range, anchor)
+}
+
+fn read_page(service: &str, mut files: Vec<LogFile>, lines: usize, range: TimeRange, anchor: PageAnchor) -> Result<LogPage, AppError> {
+
+    let (page, fallback) = match anchor {
+        PageAnchor::Tail => {
+            // An empty tail still yields a cursor for incremental refresh, short of a line
+            // still being written so that line isn't skipped
+            let end = files.last().and_then(|file| Some(LogCursor::new(file, last_line_end(&file.path).ok()?)));
+            let mut page: Vec<PositionedLine> = lines_backward(files, None, range).take(lines).collect();
+            page.reverse();
+            (page, end)
+        }
+        PageAnchor::Before(cursor) => {
+            files.truncate(locate_cursor(service, &files, cursor)? + 1);
+            let mut page: Vec<PositionedLine> = lines_backward(files, Some(cursor.offset), range).take(lines).collect();
+            page.reverse();
+            (page, Some(cursor))
+        }
+        PageAnchor::After(cursor) => {
+            files.drain(..locate_cursor(service, &files, cursor)?);
+            let page: Vec<PositionedLine> = lines_forward(files, cursor.offset, range).take(lines).collect();
+            (page, Some(cursor))
+        }
+    };
+
+    Ok(LogPage {
+        before: page.first().map(|line| line.start).or(fallback),
+        after: page.last().map(|line| line.end).or(fallback),
+        logs: page.into_iter().map(|line| line.line).collect(),
+    })
+}
+
+/// Follows svlogd's `current` file, picking up lines appended after it was opened.
+///
+/// svlogd rotates by renaming `current` to `@<tai64n>.s` and creating a fresh
+/// `current`, so the tail drains the renamed file before switching over.
+pub struct LogTail {
+    path: PathBuf,
+    file: File,
+    inode: u64,
+    /// Rotation label of the archive being drained, `None` once on the live file.
+    rotated_at: Option<u64>,
+    offset: u64,
+    partial: Vec<u8>,
+}
+
+impl LogTail {
+    /// Opens `path` positioned at its end.
+    pub fn open(path: PathBuf) -> io::Result<Self> {
+        let mut file = File::open(&path)?;
+        let inode = file.metadata()?.ino();
+        let offset = file.seek(SeekFrom::End(0))?;
+
+        Ok(Self {
+            path,
+            file,
+            inode,
+            rotated_at: None,
+            offset,
+            partial: Vec::new(),
+        })
+    }
+
+    /// Opens `file` positioned at `offset`, to continue from a cursor into it. When `file`
+    /// is an archive, the tail drains it and then switches over to `path`.
+    pub fn resume(path: PathBuf, file: &Path, rotated_at: Option<u64>, offset: u64) -> io::Result<Self> {
+        let file = File::open(file)?;
+        let inode = file.metadata()?.ino();
+
+        Ok(Self {
+            path,
+            file,
+            inode,
+            rotated_at,
+            offset,
+            partial: Vec::new(),
+        })
+    }
+
+    /// Returns the complete lines written since the previous call, each with the cursor
+    /// just past it, from which a client can resume.
+    pub fn read_new_lines(&mut self) -> io::Result<Vec<(String, LogCursor)>> {
+        let mut lines = self.drain()?;
 
-    info!("Preparing for reading last {} lines from log file {}", lines, current_log_path);
-    read_last_lines(&current_log_path, lines)
-}
\ No newline at end of file
+        // A missing `current` is transient during rotation; keep the old handle until it reappears.
+        if let Ok(metadata) = fs::metadata(&self.path) {
+            if metadata.ino() != self.inode {
+                info!("Log file {} rotated, reopening", self.path.display());
+                // The renamed file is complete, so an unterminated last line is all there is of it
+                if !self.partial.is_empty() {
+                    let line = String::from_utf8_lossy(&self.partial).into_owned();
+                    lines.push((line, self.cursor(self.offset)));
+                    self.partial.clear();
+                }
+                self.file = File::open(&self.path)?;
+                self.inode = metadata.ino();
+                self.rotated_at = None;
+                self.offset = 0;
+                lines.extend(self.drain()?);
+            } else if metadata.len() < self.offset {
+                info!("Log file {} truncated, rewinding", self.path.display());
+                self.offset = 0;
+                self.partial.clear();
+                lines.extend(self.drain()?);
+            }
+        }
+
+        Ok(lines)
+    }
+
+    fn drain(&mut self) -> io::Result<Vec<(String, LogCursor)>> {
+        self.file.seek(SeekFrom::Start(self.offset))?;
+        let read = self.file.read_to_end(&mut self.partial)?;
+        self.offset += read as u64;
+
+        // `partial` holds the bytes just before `offset` that aren't a complete line yet
+        let mut line_end = self.offset - self.partial.len() as u64;
+        let mut lines = Vec::new();
+        while let Some(position) = self.partial.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = self.partial.drain(..=position).collect();
+            line_end += line.len() as u64;
+            lines.push((
+                String::from_utf8_lossy(&line[..line.len() - 1]).into_owned(),
+                self.cursor(line_end),
+            ));
+        }
+
+        Ok(lines)
+    }
+
+    fn cursor(&self, offset: u64) -> LogCursor {
+        LogCursor { inode: self.inode, rotated_at: self.rotated_at, offset }
+    }
+}
+
+/// Follows the live log file of a service from its end, or from `after` when given, so no
+/// line written since a previous response is missed.
+pub fn service_log_tail(service_info: &ServiceInfo, after: Option<LogCursor>) -> Result<LogTail, AppError> {
+    let current_log_path = log_target(service_info)?.live_file();
+
+    info!("Following log file {}", current_log_path.display());
+    let tail = match after {
+        None => LogTail::open(current_log_path.clone()),
+        Some(cursor) => {
+            let files = service_log_files(service_info)?;
+            match files.iter().find(|file| cursor.points_into(file)) {
+                Some(file) => LogTail::resume(current_log_path.clone(), &file.path, file.rotation_label(), cursor.offset),
+                // Rotated and processed since, so everything in the live file is newer
+                None => LogTail::resume(current_log_path.clone(), &current_log_path, None, 0),
+            }
+        }
+    };
+    tail.map_err(|e| AppError::io(format!("Failed to open {}", current_log_path.display()), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::application::service_info::StateFlags;
+    use crate::domain::service::Registration;
+
+    const ARCHIVE: &str = "@400000006500000000000000.s";
+
+    fn messages(page: &LogPage) -> Vec<&str> {
+        page.logs.iter().map(|line| line.message.as_str()).collect()
+    }
+
+    /// An archive of two lines and a `current` of two lines plus one still being written.
+    fn log_dir() -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join(ARCHIVE), "a1\na2\n").unwrap();
+        fs::write(dir.path().join("current"), "c1\nc2\npart").unwrap();
+        dir
+    }
+
+    #[test]
+    fn cursor_encoding_round_trips() {
+        let live = LogCursor { inode: 0x2a, rotated_at: None, offset: 0x100 };
+        assert_eq!(live.encode(), "2a.100");
+        assert_eq!(LogCursor::decode("2a.100"), Some(live));
+
+        let archived = LogCursor { inode: 0x2a, rotated_at: Some(0x6500_0000), offset: 0 };
+        assert_eq!(archived.encode(), "2a.0.65000000");
+        assert_eq!(LogCursor::decode(&archived.encode()), Some(archived));
+
+        for invalid in ["", "2a", "2a.", "x.1", "1.2.3.4", "1.2.-3"] {
+            assert_eq!(LogCursor::decode(invalid), None, "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn pages_across_an_archive_and_a_partial_current() {
+        let dir = log_dir();
+        let files = || directory_log_files(dir.path()).unwrap();
+        let range = TimeRange::default();
+
+        let tail = read_page("demo", files(), 3, range, PageAnchor::Tail).unwrap();
+        assert_eq!(messages(&tail), ["a2", "c1", "c2"]);
+        let before = tail.before.unwrap();
+        assert_eq!((before.offset, before.rotated_at.is_some()), (3, true));
+        assert_eq!(tail.after.unwrap().offset, 6);
+
+        let older = read_page("demo", files(), 10, range, PageAnchor::Before(before)).unwrap();
+        assert_eq!(messages(&older), ["a1"]);
+        assert_eq!(older.before.unwrap().offset, 0);
+
+        let newer = read_page("demo", files(), 10, range, PageAnchor::After(older.after.unwrap())).unwrap();
+        assert_eq!(messages(&newer), ["a2", "c1", "c2"]);
+
+        // Finishing the partial line makes it the next page
+        fs::write(dir.path().join("current"), "c1\nc2\npart done\n").unwrap();
+        let next = read_page("demo", files(), 10, range, PageAnchor::After(tail.after.unwrap())).unwrap();
+        assert_eq!(messages(&next), ["part done"]);
+    }
+
+    #[test]
+    fn empty_tail_cursor_stops_before_a_partial_line() {
+        let dir = log_dir();
+        let files = directory_log_files(dir.path()).unwrap();
+
+        let tail = read_page("demo", files, 0, TimeRange::default(), PageAnchor::Tail).unwrap();
+        assert!(tail.logs.is_empty());
+        assert_eq!(tail.after.unwrap().offset, 6);
+    }
+
+    #[test]
+    fn cursor_does_not_match_a_reused_inode_or_a_mid_line_offset() {
+        let dir = log_dir();
+        let files = directory_log_files(dir.path()).unwrap();
+        let archive = &files[0];
+        let current = &files[1];
+
+        assert!(LogCursor::new(archive, 3).points_into(archive));
+        assert!(!LogCursor::new(archive, 2).points_into(archive));
+        assert!(!LogCursor::new(current, 20).points_into(current));
+
+        // The same inode under a different rotation time is a different file
+        let reused = LogCursor { rotated_at: archive.rotation_label().map(|label| label + 1), ..LogCursor::new(archive, 3) };
+        assert!(!reused.points_into(archive));
+        assert!(read_page("demo", files.clone(), 10, TimeRange::default(), PageAnchor::Before(reused)).is_err());
+
+        // A cursor taken in the live file follows it into the archive it was renamed to
+        let renamed = LogCursor { rotated_at: None, ..LogCursor::new(archive, 3) };
+        assert!(renamed.points_into(archive));
+    }
+
+    #[test]
+    fn only_identified_svlogd_directories_are_editable() {
+        let service = |log_target: LogTarget| {
+            let mut info = ServiceInfo::new(
+                "demo".to_string(),
+                "run".to_string(),
+                None,
+                None,
+                StateFlags::default(),
+                Registration::default(),
+                None,
+            );
+            info.log_target = Some(log_target);
+            info
+        };
+        let directory = |logger| LogTarget::Directory { path: PathBuf::from("/var/log/demo"), logger };
+
+        let svlogd = service(directory(Logger::Svlogd));
+        assert!(editable_svlogd_directory(&svlogd).is_ok());
+
+        let unknown = service(directory(Logger::Unknown));
+        assert!(svlogd_directory(&unknown).is_ok());
+        assert!(editable_svlogd_directory(&unknown).is_err());
+
+        let s6_log = service(directory(Logger::S6Log));
+        assert!(svlogd_directory(&s6_log).is_err());
+
+        let file = service(LogTarget::File { path: PathBuf::from("/var/log/demo.log") });
+        assert!(svlogd_directory(&file).is_err());
+    }
+}
@@ -0,0 +1,177 @@
+use std::fmt;
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use serde::{Deserialize, Serialize};
+
+use crate::config::layout::RunitLayout;
+use crate::error::AppError;
+
+/// Longest name a directory entry can have on common filesystems.
+const MAX_LENGTH: usize = 255;
+
+/// The name of a runit service, checked to name a single entry of the service directories.
+///
+/// runsvdir ignores entries starting with `.`, and `sv` takes arguments starting with `/` or
+/// `.` as paths rather than names, so names are a single path component that starts with
+/// neither. A leading `-` is refused too, so a name is never read as an `sv` option.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[serde(try_from = "String", into = "String")]
+pub struct ServiceName(String);
+
+impl ServiceName {
+    pub fn parse(name: &str) -> Result<Self, AppError> {
+        Self::try_from(name.to_string()).map_err(AppError::InvalidServiceName)
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// The service definition, `<services_dir>/<name>`. An existing definition must resolve
+    /// inside the services directory, so a symlinked definition can't point elsewhere.
+    pub fn definition_dir(&self, layout: &RunitLayout) -> Result<PathBuf, AppError> {
+        let services_dir = canonical_dir(&layout.services_dir);
+        let path = services_dir.join(&self.0);
+        match path.canonicalize() {
+            Ok(resolved) if !resolved.starts_with(&services_dir) => Err(AppError::InvalidServiceName(format!(
+                "{} resolves to {}, outside {}",
+                self,
+                resolved.display(),
+                services_dir.display()
+            ))),
+            _ => Ok(path),
+        }
+    }
+
+    /// The runsvdir entry enabling the service, `<active_dir>/<name>`. The entry itself is
+    /// always inside the active directory; where a link points is reported by `Registration`.
+    pub fn active_link(&self, layout: &RunitLayout) -> PathBuf {
+        canonical_dir(&layout.active_dir).join(&self.0)
+    }
+}
+
+/// Resolves a configured directory, keeping it as given when it doesn't exist yet.
+fn canonical_dir(dir: &str) -> PathBuf {
+    Path::new(dir).canonicalize().unwrap_or_else(|_| PathBuf::from(dir))
+}
+
+/// Describes what makes `name` unusable as a service name.
+fn problem(name: &str) -> Option<&'static str> {
+    if name.is_empty() {
+        Some("must not be empty")
+    } else if name.len() > MAX_LENGTH {
+        Some("is too long")
+    } else if name.contains('/') {
+        Some("must not contain /")
+    } else if name.starts_with('.') {
+        Some("must not start with .")
+    } else if name.starts_with('-') {
+        Some("must not start with -")
+    } else if name.chars().any(char::is_control) {
+        Some("must not contain control characters")
+    } else {
+        None
+    }
+}
+
+impl TryFrom<String> for ServiceName {
+    type Error = String;
+
+    fn try_from(name: String) -> Result<Self, String> {
+        match problem(&name) {
+            Some(problem) => Err(format!("{:?} {}", name, problem)),
+            None => Ok(Self(name)),
+        }
+    }
+}
+
+impl From<ServiceName> for String {
+    fn from(name: ServiceName) -> Self {
+        name.0
+    }
+}
+
+impl Deref for ServiceName {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for ServiceName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rejects(name: &str) -> bool {
+        matches!(ServiceName::parse(name), Err(AppError::InvalidServiceName(_)))
+    }
+
+    #[test]
+    fn accepts_plain_names() {
+        for name in ["nginx", "runit-ui", "getty@tty1", "my_app.v2", "a..b", "svc-"] {
+            assert_eq!(ServiceName::parse(name).unwrap().as_str(), name);
+        }
+    }
+
+    #[test]
+    fn rejects_path_traversal() {
+        for name in ["..", ".", "../etc", "../../etc/passwd", "foo/..", "foo/bar", "/etc", "foo/"] {
+            assert!(rejects(name), "{:?} was accepted", name);
+        }
+    }
+
+    #[test]
+    fn rejects_empty_and_hidden_names() {
+        for name in ["", ".hidden", "..hidden", "-d", "--help"] {
+            assert!(rejects(name), "{:?} was accepted", name);
+        }
+    }
+
+    #[test]
+    fn rejects_control_characters() {
+        for name in ["foo\0", "fo\0o", "foo\n", "foo\tbar", "\u{7f}"] {
+            assert!(rejects(name), "{:?} was accepted", name);
+        }
+    }
+
+    #[test]
+    fn rejects_overlong_names() {
+        assert!(ServiceName::parse(&"a".repeat(MAX_LENGTH)).is_ok());
+        assert!(rejects(&"a".repeat(MAX_LENGTH + 1)));
+    }
+
+    #[test]
+    fn definition_must_not_link_outside_services_dir() {
+        let root = std::env::temp_dir().join(format!("runit-ui-names-{}", std::process::id()));
+        let services_dir = root.join("sv");
+        std::fs::create_dir_all(services_dir.join("nginx")).unwrap();
+        std::os::unix::fs::symlink("/etc", services_dir.join("escape")).unwrap();
+        let layout = RunitLayout {
+            services_dir: services_dir.to_string_lossy().into_owned(),
+            active_dir: root.join("service").to_string_lossy().into_owned(),
+            bin_dir: root.join("bin").to_string_lossy().into_owned(),
+        };
+
+        let nginx = ServiceName::parse("nginx").unwrap().definition_dir(&layout);
+        let escape = ServiceName::parse("escape").unwrap().definition_dir(&layout);
+        let missing = ServiceName::parse("missing").unwrap().definition_dir(&layout);
+        std::fs::remove_dir_all(&root).unwrap();
+
+        assert!(nginx.unwrap().ends_with("sv/nginx"));
+        assert!(matches!(escape, Err(AppError::InvalidServiceName(_))));
+        assert!(missing.is_ok());
+    }
+
+    #[test]
+    fn deserializing_validates() {
+        assert!(serde_json::from_str::<ServiceName>("\"nginx\"").is_ok());
+        assert!(serde_json::from_str::<ServiceName>("\"../etc\"").is_err());
+    }
+}
@@ -0,0 +1,263 @@
+use std::collections::HashMap;
+use std::fs;
+use std::os::unix::fs::MetadataExt;
+use std::path::Path;
+use serde::Serialize;
+
+/// A single process as read from `/proc/<pid>`.
+#[derive(Debug, Clone)]
+pub struct ProcessEntry {
+    pub pid: u32,
+    pub ppid: u32,
+    pub comm: String,
+    pub cmdline: Vec<String>,
+    pub uid: u32,
+    pub state: char,
+    /// Resident set size in bytes.
+    pub rss: u64,
+    /// User plus system CPU time in seconds.
+    pub cpu_time: f64,
+}
+
+impl ProcessEntry {
+    pub fn read(pid: u32) -> Option<Self> {
+        let proc_dir = format!("/proc/{}", pid);
+        let uid = fs::metadata(&proc_dir).ok()?.uid();
+        let stat = fs::read_to_string(format!("{}/stat", proc_dir)).ok()?;
+
+        // The command name is enclosed in parentheses and may itself contain spaces or parentheses
+        let comm_start = stat.find('(')?;
+        let comm_end = stat.rfind(')')?;
+        let comm = stat[comm_start + 1..comm_end].to_string();
+        let fields: Vec<&str> = stat[comm_end + 1..].split_whitespace().collect();
+
+        let state = fields.first()?.chars().next()?;
+        let ppid = fields.get(1)?.parse().ok()?;
+        let utime: u64 = fields.get(11)?.parse().ok()?;
+        let stime: u64 = fields.get(12)?.parse().ok()?;
+        let rss_pages: u64 = fields.get(21)?.parse().ok()?;
+
+        let cmdline = fs::read(format!("{}/cmdline", proc_dir))
+            .map(|raw| {
+                raw.split(|&b| b == 0)
+                    .filter(|arg| !arg.is_empty())
+                    .map(|arg| String::from_utf8_lossy(arg).into_owned())
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        Some(Self {
+            pid,
+            ppid,
+            comm,
+            cmdline,
+            uid,
+            state,
+            rss: rss_pages * page_size(),
+            cpu_time: (utime + stime) as f64 / clock_ticks(),
+        })
+    }
+
+    /// Command line as a single string, falling back to `[comm]` for kernel threads and zombies.
+    pub fn command(&self) -> String {
+        if self.cmdline.is_empty() {
+            format!("[{}]", self.comm)
+        } else {
+            self.cmdline.join(" ")
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct ProcessNode {
+    pub pid: u32,
+    pub ppid: u32,
+    pub command: String,
+    pub user: String,
+    pub state: String,
+    pub rss: u64,
+    pub cpu_time: f64,
+    pub children: Vec<ProcessNode>,
+}
+
+fn page_size() -> u64 {
+    // SAFETY: sysconf has no preconditions.
+    let size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) };
+    if size > 0 { size as u64 } else { 4096 }
+}
+
+fn clock_ticks() -> f64 {
+    // SAFETY: sysconf has no preconditions.
+    let ticks = unsafe { libc::sysconf(libc::_SC_CLK_TCK) };
+    if ticks > 0 { ticks as f64 } else { 100.0 }
+}
+
+fn state_name(state: char) -> String {
+    match state {
+        'R' => "running",
+        'S' => "sleeping",
+        'D' => "disk sleep",
+        'Z' => "zombie",
+        'T' => "stopped",
+        't' => "tracing stop",
+        'X' => "dead",
+        'I' => "idle",
+        _ => "unknown",
+    }
+    .to_string()
+}
+
+/// Maps uids to user names using `/etc/passwd`.
+fn user_names() -> HashMap<u32, String> {
+    fs::read_to_string("/etc/passwd")
+        .unwrap_or_default()
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split(':');
+            let name = fields.next()?;
+            let uid = fields.nth(1)?.parse().ok()?;
+            Some((uid, name.to_string()))
+        })
+        .collect()
+}
+
+/// Reads every process currently listed in `/proc`.
+pub fn list_processes() -> Vec<ProcessEntry> {
+    fs::read_dir("/proc")
+        .map(|entries| {
+            entries
+                .flatten()
+                .filter_map(|entry| entry.file_name().to_str()?.parse().ok())
+                .filter_map(ProcessEntry::read)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// `pid` followed by the pids of all of its descendants.
+pub fn descendant_pids(pid: u32, processes: &[ProcessEntry]) -> Vec<u32> {
+    let mut pids = vec![pid];
+    let mut index = 0;
+    while index < pids.len() {
+        let parent = pids[index];
+        pids.extend(processes.iter().filter(|process| process.ppid == parent).map(|process| process.pid));
+        index += 1;
+    }
+    pids
+}
+
+/// `pid` followed by the pids of all of its descendants, read from the `children` list of each
+/// thread so only that process tree is visited. Falls back to scanning every process on kernels
+/// built without those lists.
+pub fn tree_pids(pid: u32) -> Vec<u32> {
+    if !Path::new("/proc/thread-self/children").exists() {
+        return descendant_pids(pid, &list_processes());
+    }
+
+    let mut pids = vec![pid];
+    let mut index = 0;
+    while index < pids.len() {
+        let children = child_pids(pids[index]);
+        pids.extend(children);
+        index += 1;
+    }
+    pids
+}
+
+/// Children of every thread of `pid`; empty once the process has exited.
+fn child_pids(pid: u32) -> Vec<u32> {
+    let Ok(tasks) = fs::read_dir(format!("/proc/{}/task", pid)) else {
+        return Vec::new();
+    };
+    tasks
+        .flatten()
+        .filter_map(|task| fs::read_to_string(task.path().join("children")).ok())
+        .flat_map(|children| {
+            children
+                .split_whitespace()
+                .filter_map(|child| child.parse().ok())
+                .collect::<Vec<u32>>()
+        })
+        .collect()
+}
+
+/// Finds the runsv process supervising `service_dir`.
+///
+/// runsv runs with the service directory as its working directory; when that
+/// cannot be read the parent of the supervised process is used instead.
+fn find_runsv(service_dir: &Path, service_pid: Option<u32>, processes: &[ProcessEntry]) -> Option<u32> {
+    let service_dir = service_dir.canonicalize().ok()?;
+
+    processes
+        .iter()
+        .filter(|process| process.comm == "runsv")
+        .find(|process| {
+            fs::read_link(format!("/proc/{}/cwd", process.pid))
+                .is_ok_and(|cwd| cwd == service_dir)
+        })
+        .map(|process| process.pid)
+        .or_else(|| {
+            let service_pid = service_pid?;
+            processes
+                .iter()
+                .find(|process| process.pid == service_pid)
+                .map(|process| process.ppid)
+        })
+}
+
+fn build_node(
+    process: &ProcessEntry,
+    children: &HashMap<u32, Vec<&ProcessEntry>>,
+    users: &HashMap<u32, String>,
+) -> ProcessNode {
+    ProcessNode {
+        pid: process.pid,
+        ppid: process.ppid,
+        command: process.command(),
+        user: users.get(&process.uid).cloned().unwrap_or_else(|| process.uid.to_string()),
+        state: state_name(process.state),
+        rss: process.rss,
+        cpu_time: process.cpu_time,
+        children: children
+            .get(&process.pid)
+            .map(|procs| procs.iter().map(|child| build_node(child, children, users)).collect())
+            .unwrap_or_default(),
+    }
+}
+
+/// Builds the process tree rooted at the runsv supervising `service_dir`.
+pub fn service_tree(service_dir: &Path, service_pid: Option<u32>) -> Option<ProcessNode> {
+    let processes = list_processes();
+    let runsv_pid = find_runsv(service_dir, service_pid, &processes)?;
+    let root = processes.iter().find(|process| process.pid == runsv_pid)?;
+
+    let mut children: HashMap<u32, Vec<&ProcessEntry>> = HashMap::new();
+    for process in &processes {
+        children.entry(process.ppid).or_default().push(process);
+    }
+    for procs in children.values_mut() {
+        procs.sort_by_key(|process| process.pid);
+    }
+
+    Some(build_node(root, &children, &user_names()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process::Command;
+
+    #[test]
+    fn tree_pids_finds_children_like_a_full_scan() {
+        let mut child = Command::new("sleep").arg("5").spawn().unwrap();
+        let pid = std::process::id();
+
+        let pids = tree_pids(pid);
+        assert_eq!(pids[0], pid);
+        assert!(pids.contains(&child.id()));
+        assert!(descendant_pids(pid, &list_processes()).contains(&child.id()));
+
+        child.kill().unwrap();
+        child.wait().unwrap();
+    }
+}
@@ -0,0 +1,175 @@
+use std::fs::{self, OpenOptions};
+use std::io;
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::Path;
+use std::time::SystemTime;
+use serde::Serialize;
+
+use crate::domain::tai64;
+
+/// Size of the binary `supervise/status` record written by runsv.
+const STATUS_SIZE: usize = 20;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RunState {
+    Down,
+    Run,
+    Finish,
+}
+
+impl RunState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RunState::Down => "down",
+            RunState::Run => "run",
+            RunState::Finish => "finish",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Want {
+    Up,
+    Down,
+}
+
+/// Decoded contents of runsv's `supervise/status` file.
+///
+/// Layout (20 bytes): TAI64N timestamp of the last state change (12 bytes),
+/// pid (4 bytes, little-endian), paused flag, want byte (`u`/`d`),
+/// term flag and the run state (0 = down, 1 = run, 2 = finish).
+#[derive(Debug, Clone)]
+pub struct SuperviseStatus {
+    pub changed_at: SystemTime,
+    pub pid: Option<u32>,
+    pub paused: bool,
+    pub want: Option<Want>,
+    pub got_term: bool,
+    pub state: RunState,
+}
+
+impl SuperviseStatus {
+    /// Reads `<service_dir>/supervise/status`.
+    pub fn read(service_dir: &Path) -> io::Result<Self> {
+        let bytes = fs::read(service_dir.join("supervise").join("status"))?;
+        Self::from_bytes(&bytes)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> io::Result<Self> {
+        if bytes.len() != STATUS_SIZE {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Unexpected supervise/status size: {} bytes", bytes.len()),
+            ));
+        }
+
+        let changed_at = tai64::decode_tai64n(&bytes[0..12])
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Invalid TAI64N timestamp"))?;
+        let pid = u32::from_le_bytes([bytes[12], bytes[13], bytes[14], bytes[15]]);
+        let want = match bytes[17] {
+            b'u' => Some(Want::Up),
+            b'd' => Some(Want::Down),
+            _ => None,
+        };
+        let state = match bytes[19] {
+            0 => RunState::Down,
+            1 => RunState::Run,
+            2 => RunState::Finish,
+            other => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("Unknown run state: {}", other),
+                ))
+            }
+        };
+
+        Ok(Self {
+            changed_at,
+            pid: (pid != 0).then_some(pid),
+            paused: bytes[16] != 0,
+            want,
+            got_term: bytes[18] != 0,
+            state,
+        })
+    }
+
+    /// Seconds elapsed since the last state change, as printed by `sv status`.
+    pub fn uptime(&self) -> u64 {
+        SystemTime::now()
+            .duration_since(self.changed_at)
+            .map(|d| d.as_secs())
+            .unwrap_or(0)
+    }
+}
+
+/// Whether a runsv process is supervising `service_dir`.
+///
+/// runsv keeps `supervise/ok` open for reading, so opening the fifo for
+/// writing without blocking only succeeds while runsv is alive (this is the
+/// same check `sv` uses to print "runsv not running").
+pub fn is_supervised(service_dir: &Path) -> bool {
+    OpenOptions::new()
+        .write(true)
+        .custom_flags(libc::O_NONBLOCK)
+        .open(service_dir.join("supervise").join("ok"))
+        .is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    /// A record as runsv writes it: changed at 1600000000.5, the given pid, flags and state.
+    fn record(pid: u32, paused: u8, want: u8, term: u8, state: u8) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&0x4000_0000_5f5e_100a_u64.to_be_bytes());
+        bytes.extend_from_slice(&500_000_000_u32.to_be_bytes());
+        bytes.extend_from_slice(&pid.to_le_bytes());
+        bytes.extend_from_slice(&[paused, want, term, state]);
+        bytes
+    }
+
+    #[test]
+    fn decodes_running_service() {
+        let status = SuperviseStatus::from_bytes(&record(4242, 0, b'u', 0, 1)).unwrap();
+        assert_eq!(status.changed_at, UNIX_EPOCH + Duration::new(1_600_000_000, 500_000_000));
+        assert_eq!(status.pid, Some(4242));
+        assert!(!status.paused);
+        assert_eq!(status.want, Some(Want::Up));
+        assert!(!status.got_term);
+        assert_eq!(status.state, RunState::Run);
+    }
+
+    #[test]
+    fn decodes_flags() {
+        let status = SuperviseStatus::from_bytes(&record(0x0102_0304, 1, b'd', 1, 2)).unwrap();
+        assert_eq!(status.pid, Some(0x0102_0304));
+        assert!(status.paused);
+        assert_eq!(status.want, Some(Want::Down));
+        assert!(status.got_term);
+        assert_eq!(status.state, RunState::Finish);
+    }
+
+    #[test]
+    fn down_service_has_no_pid() {
+        let status = SuperviseStatus::from_bytes(&record(0, 0, 0, 0, 0)).unwrap();
+        assert_eq!(status.pid, None);
+        assert_eq!(status.want, None);
+        assert_eq!(status.state, RunState::Down);
+    }
+
+    #[test]
+    fn rejects_malformed_records() {
+        let mut short = record(1, 0, b'u', 0, 1);
+        short.pop();
+        assert!(SuperviseStatus::from_bytes(&short).is_err());
+        assert!(SuperviseStatus::from_bytes(&record(1, 0, b'u', 0, 3)).is_err());
+
+        let mut bad_nanos = record(1, 0, b'u', 0, 1);
+        bad_nanos[8..12].copy_from_slice(&1_000_000_000_u32.to_be_bytes());
+        assert!(SuperviseStatus::from_bytes(&bad_nanos).is_err());
+    }
+}
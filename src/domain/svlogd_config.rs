@@ -0,0 +1,332 @@
+use std::fs;
+use std::io;
+use std::net::Ipv4Addr;
+use std::path::Path;
+use std::str::FromStr;
+use serde::{Deserialize, Serialize};
+
+/// Leading characters svlogd interprets; other lines are ignored.
+const DIRECTIVES: &str = "snNt!uUp+-eE";
+
+/// How a line selection pattern applies, by its leading character.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PatternKind {
+    /// `+`: select matching lines for the log directory.
+    Select,
+    /// `-`: deselect matching lines.
+    Deselect,
+    /// `e`: select matching lines for alerts on standard error.
+    AlertSelect,
+    /// `E`: deselect matching lines for alerts.
+    AlertDeselect,
+}
+
+impl PatternKind {
+    fn prefix(&self) -> char {
+        match self {
+            Self::Select => '+',
+            Self::Deselect => '-',
+            Self::AlertSelect => 'e',
+            Self::AlertDeselect => 'E',
+        }
+    }
+}
+
+/// A line selection pattern; svlogd applies them in order, the last match wins.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct LinePattern {
+    pub kind: PatternKind,
+    pub pattern: String,
+}
+
+/// Forwarding of selected lines to a syslog server.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct UdpForward {
+    pub address: String,
+    pub port: Option<u16>,
+    /// `U` keeps writing lines to the log directory; with `u` they are only stored when sending fails.
+    #[serde(default)]
+    pub also_store: bool,
+}
+
+/// The settings of an svlogd `config` file.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct SvlogdConfig {
+    /// `s`: rotate `current` once it reaches this many bytes; 0 disables rotation.
+    pub size: Option<u64>,
+    /// `n`: old log files to keep; 0 keeps all.
+    pub count: Option<u32>,
+    /// `N`: old log files to keep even when out of disk space.
+    pub min_count: Option<u32>,
+    /// `t`: rotate a non-empty `current` after this many seconds.
+    pub timeout: Option<u64>,
+    /// `!`: processor run on each rotated file.
+    pub processor: Option<String>,
+    /// `u`/`U`: forward selected lines over UDP.
+    pub udp: Option<UdpForward>,
+    /// `p`: prefix for forwarded and alerted lines.
+    pub prefix: Option<String>,
+    #[serde(default)]
+    pub patterns: Vec<LinePattern>,
+    /// Comments and lines svlogd ignores, kept when the file is written back.
+    #[serde(default)]
+    pub other: Vec<String>,
+}
+
+fn parse_number<T: FromStr>(what: &str, value: &str) -> Result<T, String> {
+    value.parse().map_err(|_| format!("invalid {} {}", what, value))
+}
+
+fn parse_udp(value: &str, also_store: bool) -> Result<UdpForward, String> {
+    let (address, port) = match value.split_once(':') {
+        Some((address, port)) => (address, Some(port.parse().map_err(|_| format!("invalid UDP port {}", port))?)),
+        None => (value, None),
+    };
+
+    Ok(UdpForward {
+        address: address.to_string(),
+        port,
+        also_store,
+    })
+}
+
+impl SvlogdConfig {
+    /// Parses a config file. Lines that can't be understood are kept in `other` and
+    /// reported, so a broken file can still be shown and fixed.
+    pub fn parse(content: &str) -> (Self, Vec<String>) {
+        let mut config = Self::default();
+        let mut problems = Vec::new();
+
+        for (index, line) in content.lines().enumerate() {
+            let Some(directive) = line.chars().next() else {
+                continue;
+            };
+            let value = &line[directive.len_utf8()..];
+
+            let result = match directive {
+                's' => parse_number("size", value).map(|size| config.size = Some(size)),
+                'n' => parse_number("count", value).map(|count| config.count = Some(count)),
+                'N' => parse_number("minimum count", value).map(|min_count| config.min_count = Some(min_count)),
+                't' => parse_number("timeout", value).map(|timeout| config.timeout = Some(timeout)),
+                '!' => {
+                    config.processor = Some(value.to_string());
+                    Ok(())
+                }
+                'u' | 'U' => parse_udp(value, directive == 'U').map(|udp| config.udp = Some(udp)),
+                'p' => {
+                    config.prefix = Some(value.to_string());
+                    Ok(())
+                }
+                '+' | '-' | 'e' | 'E' => {
+                    let kind = match directive {
+                        '+' => PatternKind::Select,
+                        '-' => PatternKind::Deselect,
+                        'e' => PatternKind::AlertSelect,
+                        _ => PatternKind::AlertDeselect,
+                    };
+                    config.patterns.push(LinePattern { kind, pattern: value.to_string() });
+                    Ok(())
+                }
+                _ => {
+                    config.other.push(line.to_string());
+                    Ok(())
+                }
+            };
+
+            if let Err(problem) = result {
+                problems.push(format!("line {}: {}", index + 1, problem));
+                config.other.push(line.to_string());
+            }
+        }
+
+        (config, problems)
+    }
+
+    /// Checks the settings against what svlogd accepts.
+    pub fn validate(&self) -> Result<(), Vec<String>> {
+        let mut problems = Vec::new();
+
+        if let (Some(count), Some(min_count)) = (self.count, self.min_count) {
+            if count > 0 && min_count > count {
+                problems.push(format!("minimum count {} exceeds count {}", min_count, count));
+            }
+        }
+        if self.processor.as_ref().is_some_and(|processor| processor.trim().is_empty()) {
+            problems.push("processor must not be empty".to_string());
+        }
+        if let Some(udp) = &self.udp {
+            if udp.address.parse::<Ipv4Addr>().is_err() {
+                problems.push(format!("UDP address {} is not an IPv4 address", udp.address));
+            }
+            if udp.port == Some(0) {
+                problems.push("UDP port must not be 0".to_string());
+            }
+        }
+        if self.patterns.iter().any(|pattern| pattern.pattern.is_empty()) {
+            problems.push("patterns must not be empty".to_string());
+        }
+
+        let texts = self.processor.iter()
+            .chain(self.prefix.iter())
+            .chain(self.patterns.iter().map(|pattern| &pattern.pattern))
+            .chain(self.other.iter());
+        if texts.into_iter().any(|text| text.contains('\n')) {
+            problems.push("values must be single lines".to_string());
+        }
+        for line in &self.other {
+            if line.starts_with(|c| DIRECTIVES.contains(c)) {
+                problems.push(format!("{} would be read as a setting, not a comment", line));
+            }
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(problems)
+        }
+    }
+
+    /// Renders the config file, with kept comments first.
+    pub fn render(&self) -> String {
+        let mut lines: Vec<String> = self.other.clone();
+
+        lines.extend(self.size.map(|size| format!("s{}", size)));
+        lines.extend(self.count.map(|count| format!("n{}", count)));
+        lines.extend(self.min_count.map(|min_count| format!("N{}", min_count)));
+        lines.extend(self.timeout.map(|timeout| format!("t{}", timeout)));
+        lines.extend(self.processor.as_ref().map(|processor| format!("!{}", processor)));
+        lines.extend(self.udp.as_ref().map(|udp| {
+            let directive = if udp.also_store { 'U' } else { 'u' };
+            match udp.port {
+                Some(port) => format!("{}{}:{}", directive, udp.address, port),
+                // svlogd defaults to the syslog port
+                None => format!("{}{}", directive, udp.address),
+            }
+        }));
+        lines.extend(self.prefix.as_ref().map(|prefix| format!("p{}", prefix)));
+        lines.extend(self.patterns.iter().map(|pattern| format!("{}{}", pattern.kind.prefix(), pattern.pattern)));
+
+        lines.into_iter().map(|line| line + "\n").collect()
+    }
+
+    /// Reads `<log_dir>/config`; a missing file means svlogd's defaults.
+    pub fn read(log_dir: &Path) -> io::Result<(Self, Vec<String>)> {
+        match fs::read_to_string(log_dir.join("config")) {
+            Ok(content) => Ok(Self::parse(&content)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok((Self::default(), Vec::new())),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Replaces `<log_dir>/config` atomically, so svlogd never reads a partial file.
+    pub fn write(&self, log_dir: &Path) -> io::Result<()> {
+        let staging = log_dir.join("config.runit-ui");
+        fs::write(&staging, self.render())?;
+        fs::rename(&staging, log_dir.join("config"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FIXTURE: &str = "\
+# rotate daily, keep a week
+s1000000
+n7
+N2
+t86400
+!gzip
+U10.0.0.1:514
+pweb:
++*error*
+-*debug*
+e*fatal*
+Eok*
+sbig
+u10.0.0.2:port
+";
+
+    #[test]
+    fn parses_directives_comments_and_invalid_lines() {
+        let (config, problems) = SvlogdConfig::parse(FIXTURE);
+
+        assert_eq!(config.size, Some(1_000_000));
+        assert_eq!(config.count, Some(7));
+        assert_eq!(config.min_count, Some(2));
+        assert_eq!(config.timeout, Some(86_400));
+        assert_eq!(config.processor.as_deref(), Some("gzip"));
+        assert_eq!(
+            config.udp,
+            Some(UdpForward { address: "10.0.0.1".to_string(), port: Some(514), also_store: true })
+        );
+        assert_eq!(config.prefix.as_deref(), Some("web:"));
+        assert_eq!(
+            config.patterns,
+            vec![
+                LinePattern { kind: PatternKind::Select, pattern: "*error*".to_string() },
+                LinePattern { kind: PatternKind::Deselect, pattern: "*debug*".to_string() },
+                LinePattern { kind: PatternKind::AlertSelect, pattern: "*fatal*".to_string() },
+                LinePattern { kind: PatternKind::AlertDeselect, pattern: "ok*".to_string() },
+            ]
+        );
+        assert_eq!(config.other, vec!["# rotate daily, keep a week", "sbig", "u10.0.0.2:port"]);
+        assert_eq!(problems, vec!["line 13: invalid size big", "line 14: invalid UDP port port"]);
+    }
+
+    #[test]
+    fn invalid_lines_kept_from_parsing_fail_validation() {
+        let (config, _) = SvlogdConfig::parse(FIXTURE);
+        let problems = config.validate().unwrap_err();
+        assert_eq!(
+            problems,
+            vec![
+                "sbig would be read as a setting, not a comment",
+                "u10.0.0.2:port would be read as a setting, not a comment",
+            ]
+        );
+    }
+
+    #[test]
+    fn round_trips_through_render() {
+        let content = FIXTURE.replace("sbig\nu10.0.0.2:port\n", "");
+        let (config, problems) = SvlogdConfig::parse(&content);
+        assert!(problems.is_empty());
+        assert_eq!(config.validate(), Ok(()));
+
+        let rendered = config.render();
+        assert_eq!(rendered, content);
+        assert_eq!(SvlogdConfig::parse(&rendered), (config, Vec::new()));
+    }
+
+    #[test]
+    fn empty_file_is_svlogd_defaults() {
+        assert_eq!(SvlogdConfig::parse(""), (SvlogdConfig::default(), Vec::new()));
+        assert_eq!(SvlogdConfig::default().render(), "");
+    }
+
+    #[test]
+    fn validate_reports_every_problem() {
+        let config = SvlogdConfig {
+            count: Some(2),
+            min_count: Some(5),
+            processor: Some(" ".to_string()),
+            udp: Some(UdpForward { address: "loghost".to_string(), port: Some(0), also_store: false }),
+            prefix: Some("two\nlines".to_string()),
+            patterns: vec![LinePattern { kind: PatternKind::Select, pattern: String::new() }],
+            ..SvlogdConfig::default()
+        };
+        assert_eq!(
+            config.validate().unwrap_err(),
+            vec![
+                "minimum count 5 exceeds count 2",
+                "processor must not be empty",
+                "UDP address loghost is not an IPv4 address",
+                "UDP port must not be 0",
+                "patterns must not be empty",
+                "values must be single lines",
+            ]
+        );
+    }
+}
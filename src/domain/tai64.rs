@@ -0,0 +1,75 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// TAI64 label of the UNIX epoch as used by runit, which assumes the
+/// 10 second TAI/UTC offset of 1970.
+const TAI64_UNIX_EPOCH: u64 = 4_611_686_018_427_387_914;
+
+/// Decodes a 12 byte packed TAI64N timestamp (8 bytes of seconds followed by
+/// 4 bytes of nanoseconds, both big-endian) into a `SystemTime`.
+pub fn decode_tai64n(bytes: &[u8]) -> Option<SystemTime> {
+    if bytes.len() < 12 {
+        return None;
+    }
+
+    let seconds = u64::from_be_bytes(bytes[0..8].try_into().ok()?);
+    let nanos = u32::from_be_bytes(bytes[8..12].try_into().ok()?);
+    if nanos >= 1_000_000_000 {
+        return None;
+    }
+
+    let unix_seconds = seconds.checked_sub(TAI64_UNIX_EPOCH)?;
+    UNIX_EPOCH.checked_add(Duration::new(unix_seconds, nanos))
+}
+
+/// Decodes the external `@` + 24 hex digit TAI64N label svlogd prefixes with `-t`.
+pub fn parse_tai64n_label(label: &str) -> Option<SystemTime> {
+    let hex = label.strip_prefix('@')?;
+    if hex.len() != 24 || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+
+    let mut bytes = [0u8; 12];
+    for (index, byte) in bytes.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&hex[index * 2..index * 2 + 2], 16).ok()?;
+    }
+    decode_tai64n(&bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_packed_timestamp() {
+        let mut bytes = [0u8; 12];
+        bytes[..8].copy_from_slice(&0x4000_0000_5f5e_100a_u64.to_be_bytes());
+        bytes[8..].copy_from_slice(&123_456_789_u32.to_be_bytes());
+        assert_eq!(decode_tai64n(&bytes), Some(UNIX_EPOCH + Duration::new(1_600_000_000, 123_456_789)));
+        assert_eq!(decode_tai64n(&bytes[..11]), None);
+    }
+
+    #[test]
+    fn parses_label() {
+        assert_eq!(
+            parse_tai64n_label("@400000005f5e100a075bcd15"),
+            Some(UNIX_EPOCH + Duration::new(1_600_000_000, 123_456_789))
+        );
+        assert_eq!(parse_tai64n_label("@400000000000000a00000000"), Some(UNIX_EPOCH));
+    }
+
+    #[test]
+    fn rejects_malformed_labels() {
+        for label in [
+            "400000005f5e100a075bcd15",
+            "@400000005f5e100a075bcd1",
+            "@400000005f5e100a075bcd155",
+            "@400000005f5e100a075bcdzz",
+            // Nanoseconds beyond a second
+            "@400000005f5e100a3b9aca00",
+            // Before the UNIX epoch
+            "@4000000000000000075bcd15",
+        ] {
+            assert_eq!(parse_tai64n_label(label), None, "{} was accepted", label);
+        }
+    }
+}
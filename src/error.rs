@@ -0,0 +1,122 @@
+use std::fmt;
+use std::io;
+use actix_web::http::StatusCode;
+use actix_web::{HttpResponse, ResponseError};
+use serde_json::json;
+
+/// Errors surfaced by service operations and mapped onto HTTP responses by the API.
+#[derive(Debug)]
+pub enum AppError {
+    ServiceNotFound(String),
+    /// A service name that isn't a single entry of the service directories.
+    InvalidServiceName(String),
+    NoLogService(String),
+    LogNotReadable { service: String, reason: String },
+    SvFailure { service: String, message: String, stdout: String, stderr: String },
+    InvalidAction(String),
+    InvalidQuery(String),
+    /// A request body that isn't the expected JSON.
+    InvalidBody(String),
+    /// A submitted configuration failed validation; one message per problem.
+    InvalidConfig(Vec<String>),
+    /// The access policy doesn't allow the user to do this.
+    Forbidden(String),
+    /// The operating system refused access, e.g. to a file.
+    PermissionDenied(String),
+    /// An operation didn't finish within its time limit.
+    Timeout(String),
+    Io { context: String, source: io::Error },
+}
+
+impl AppError {
+    /// Wraps an I/O error, keeping permission failures distinguishable.
+    pub fn io(context: impl Into<String>, source: io::Error) -> Self {
+        let context = context.into();
+        match source.kind() {
+            io::ErrorKind::PermissionDenied => AppError::PermissionDenied(format!("{}: {}", context, source)),
+            _ => AppError::Io { context, source },
+        }
+    }
+
+    /// Stable machine-readable identifier included in the JSON error body.
+    pub fn code(&self) -> &'static str {
+        match self {
+            AppError::ServiceNotFound(_) => "service_not_found",
+            AppError::InvalidServiceName(_) => "invalid_service_name",
+            AppError::NoLogService(_) => "no_log_service",
+            AppError::LogNotReadable { .. } => "log_not_readable",
+            AppError::SvFailure { .. } => "sv_failure",
+            AppError::InvalidAction(_) => "invalid_action",
+            AppError::InvalidQuery(_) => "invalid_query",
+            AppError::InvalidBody(_) => "invalid_body",
+            AppError::InvalidConfig(_) => "invalid_config",
+            AppError::Forbidden(_) => "forbidden",
+            AppError::PermissionDenied(_) => "permission_denied",
+            AppError::Timeout(_) => "timeout",
+            AppError::Io { .. } => "io_error",
+        }
+    }
+}
+
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AppError::ServiceNotFound(service) => write!(f, "Service {} not found", service),
+            AppError::InvalidServiceName(message) => write!(f, "Invalid service name: {}", message),
+            AppError::NoLogService(service) => write!(f, "Service {} has no log service", service),
+            AppError::LogNotReadable { service, reason } => {
+                write!(f, "Could not read logs for service {}: {}", service, reason)
+            }
+            AppError::SvFailure { service, message, stdout, stderr } => {
+                write!(f, "sv failed for service {}: {}", service, message)?;
+                for output in [stdout.trim(), stderr.trim()] {
+                    if !output.is_empty() {
+                        write!(f, ": {}", output)?;
+                    }
+                }
+                Ok(())
+            }
+            AppError::InvalidAction(action) => write!(f, "Invalid action: {}", action),
+            AppError::InvalidQuery(message) => write!(f, "Invalid query: {}", message),
+            AppError::InvalidBody(message) => write!(f, "Invalid request body: {}", message),
+            AppError::InvalidConfig(problems) => write!(f, "Invalid configuration: {}", problems.join("; ")),
+            AppError::Forbidden(message) => write!(f, "Forbidden: {}", message),
+            AppError::PermissionDenied(message) => write!(f, "Permission denied: {}", message),
+            AppError::Timeout(operation) => write!(f, "Timed out: {}", operation),
+            AppError::Io { context, source } => write!(f, "{}: {}", context, source),
+        }
+    }
+}
+
+impl std::error::Error for AppError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            AppError::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+impl ResponseError for AppError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            AppError::ServiceNotFound(_) | AppError::NoLogService(_) => StatusCode::NOT_FOUND,
+            AppError::LogNotReadable { .. } => StatusCode::CONFLICT,
+            AppError::InvalidServiceName(_)
+            | AppError::InvalidAction(_)
+            | AppError::InvalidQuery(_)
+            | AppError::InvalidBody(_)
+            | AppError::InvalidConfig(_) => StatusCode::BAD_REQUEST,
+            AppError::Forbidden(_) | AppError::PermissionDenied(_) => StatusCode::FORBIDDEN,
+            AppError::Timeout(_) => StatusCode::GATEWAY_TIMEOUT,
+            AppError::SvFailure { .. } | AppError::Io { .. } => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        HttpResponse::build(self.status_code()).json(json!({
+            "error": self.to_string(),
+            "code": self.code(),
+        }))
+    }
+}
@@ -1,7 +1,10 @@
 use actix_web::{web, App, HttpServer};
+use actix_web::middleware::Condition;
 use actix_web_httpauth::middleware::HttpAuthentication;
-use actix_web_httpauth::extractors::basic::BasicAuth;
+use actix_web_httpauth::extractors::basic::{self, BasicAuth};
+use actix_web_httpauth::extractors::AuthenticationError;
 use actix_web::dev::ServiceRequest;
+use actix_web::HttpMessage;
 use actix_web::HttpResponse;
 use anyhow::{Context, Result};
 use tera::Tera;
@@ -9,12 +12,20 @@ use include_dir::{include_dir, Dir, DirEntry};
 use env_logger::{Builder, Target};
 use log::info;
 use clap::Parser;
+use std::sync::Arc;
 
+use application::access_control::AccessPolicy;
+use application::audit::AuditLog;
+use application::auth::{AuthenticatedUser, Authenticator};
+use application::state_cache::StateCache;
 use config::app_config::AppConfig;
+use config::layout::{LayoutKind, RunitLayout};
+use domain::service_name::ServiceName;
 
 mod application;
 mod domain;
 mod config;
+mod error;
 mod presentation;
 
 static TEMPLATES_DIR: Dir = include_dir!("$CARGO_MANIFEST_DIR/src/presentation/templates");
@@ -37,8 +48,8 @@ struct Args {
     install: bool,
 
     /// The name of the service to install
-    #[arg(long, default_value = "runit-ui")]
-    service_name: String,
+    #[arg(long, default_value = "runit-ui", value_parser = ServiceName::parse)]
+    service_name: ServiceName,
 
     /// The directory for log files
     #[arg(long, default_value = "/var/log/runit-ui")]
@@ -49,9 +60,54 @@ struct Args {
     #[arg(long, default_value = "info")]
     log_level: String,
 
-    /// The directory for service files
-    #[arg(long, default_value = "/etc/sv")]
-    services_dir: String,
+    /// The runit directory layout; explicit directory flags override it
+    #[arg(long, value_enum, default_value = "auto")]
+    layout: LayoutKind,
+
+    /// The directory for service files (defaults to the layout's, e.g. /etc/sv)
+    #[arg(long)]
+    services_dir: Option<String>,
+
+    /// The directory runsvdir supervises (defaults to the layout's, e.g. /etc/service)
+    #[arg(long)]
+    active_dir: Option<String>,
+
+    /// The directory the installer copies the binary into (defaults to the layout's, e.g. /usr/local/bin)
+    #[arg(long)]
+    bin_dir: Option<String>,
+
+    /// Seconds sv waits for a service to reach the requested state (sv -w)
+    #[arg(long, default_value = "7")]
+    sv_timeout: u64,
+
+    /// Seconds a service status query may take before the request fails
+    #[arg(long, default_value = "5")]
+    status_timeout: u64,
+
+    /// Seconds reading, searching or downloading logs may take before the request fails
+    #[arg(long, default_value = "60")]
+    log_timeout: u64,
+
+    /// Number of services whose status is queried concurrently when listing services
+    #[arg(long, default_value = "16", value_parser = clap::value_parser!(u16).range(1..))]
+    status_concurrency: u16,
+
+    /// Seconds after which cached service states are re-read; the only refresh where inotify is unavailable
+    #[arg(long, default_value = "30", value_parser = clap::value_parser!(u64).range(1..))]
+    stale_after: u64,
+
+    /// Seconds within which restarts of a service are counted for flapping detection; without
+    /// inotify, restarts between two reads count once, so keep it well above --stale-after
+    #[arg(long, default_value = "60", value_parser = clap::value_parser!(u64).range(1..))]
+    flap_window: u64,
+
+    /// Restarts within the window from which a service is marked as flapping
+    #[arg(long, default_value = "3", value_parser = clap::value_parser!(u16).range(1..))]
+    flap_threshold: u16,
+
+    /// Take a service down with `sv down` after this many restarts within the window
+    #[arg(long, value_parser = clap::value_parser!(u16).range(1..))]
+    flap_stop_after: Option<u16>,
 
     /// The username for basic authentication
     #[arg(long)]
@@ -61,6 +117,22 @@ struct Args {
     #[arg(long)]
     password: Option<String>,
 
+    /// An htpasswd-style file with bcrypt or argon2 password hashes
+    #[arg(long, conflicts_with_all = ["username", "password"])]
+    htpasswd_file: Option<String>,
+
+    /// A TOML, YAML or JSON file mapping users to roles per service
+    #[arg(long)]
+    acl_file: Option<String>,
+
+    /// The append-only JSON lines file recording every service action
+    #[arg(long, default_value = "/var/lib/runit-ui/audit.jsonl")]
+    audit_file: String,
+
+    /// Serve every route without authentication
+    #[arg(long, default_value = "false", conflicts_with_all = ["username", "password", "htpasswd_file"])]
+    no_auth: bool,
+
     // The application bind address
     #[arg(long, default_value = "0.0.0.0:8080")]
     bind: String,
@@ -70,23 +142,40 @@ async fn basic_auth_validator(
     req: ServiceRequest,
     credentials: BasicAuth,
 ) -> Result<ServiceRequest, (actix_web::Error, ServiceRequest)> {
-    let username = credentials.user_id();
-    let password = credentials.password().map_or("", |v| v);
-
-    // Borrow the app data instead of moving the request
-    if let Some(app_data) = req.app_data::<web::Data<AppConfig>>() {
-        // Check if username and password are configured
-        if let (Some(expected_username), Some(expected_password)) = (&app_data.username, &app_data.password) {
-            if username == expected_username && password == expected_password {
-                return Ok(req);
-            }
-        } else {
-            // If credentials are not configured, bypass authentication
+    if let Some(authenticator) = req.app_data::<web::Data<Authenticator>>().cloned() {
+        let username = credentials.user_id().to_string();
+        let password = credentials.password().map_or("", |v| v).to_string();
+
+        // Password hashing is CPU bound, keep it off the worker thread
+        let verified = {
+            let username = username.clone();
+            web::block(move || authenticator.verify(&username, &password))
+                .await
+                .unwrap_or(false)
+        };
+        if verified {
+            req.extensions_mut().insert(AuthenticatedUser(username));
             return Ok(req);
         }
     }
 
-    Err((actix_web::error::ErrorUnauthorized("Invalid username or password"), req))
+    let challenge = basic::Config::default().realm("runit-ui");
+    Err((AuthenticationError::from(challenge).into(), req))
+}
+
+fn build_authenticator(args: &Args) -> Result<Authenticator, String> {
+    if args.no_auth {
+        return Ok(Authenticator::disabled());
+    }
+
+    if let Some(htpasswd_file) = &args.htpasswd_file {
+        return Authenticator::from_htpasswd(htpasswd_file);
+    }
+
+    match (&args.username, &args.password) {
+        (Some(username), Some(password)) => Ok(Authenticator::from_plaintext(username, password)),
+        _ => Err("No credentials configured: pass --htpasswd-file, --username and --password, or --no-auth".to_string()),
+    }
 }
 
 fn load_embedded_templates() -> Result<Tera> {
@@ -115,16 +204,33 @@ fn load_embedded_templates() -> Result<Tera> {
     Ok(tera)
 }
 
-fn handle_installation(args: &Args) -> Result<(), Box<dyn std::error::Error>> {
-    application::installer::install_service(args)
+fn resolve_layout(args: &Args) -> RunitLayout {
+    let mut layout = RunitLayout::for_kind(args.layout);
+    if let Some(services_dir) = &args.services_dir {
+        layout.services_dir = services_dir.clone();
+    }
+    if let Some(active_dir) = &args.active_dir {
+        layout.active_dir = active_dir.clone();
+    }
+    if let Some(bin_dir) = &args.bin_dir {
+        layout.bin_dir = bin_dir.clone();
+    }
+    layout
+}
+
+fn handle_installation(args: &Args, layout: &RunitLayout) -> Result<(), Box<dyn std::error::Error>> {
+    // The installed service would exit on start without credentials, and runsv restart it in a loop
+    build_authenticator(args)?;
+    application::installer::install_service(args, layout)
 }
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
     let args = Args::parse();
+    let layout = resolve_layout(&args);
 
     if args.install {
-        if let Err(e) = handle_installation(&args) {
+        if let Err(e) = handle_installation(&args, &layout) {
             eprintln!("Failed to install service: {}", e);
             std::process::exit(1);
         }
@@ -132,11 +238,44 @@ async fn main() -> std::io::Result<()> {
         return Ok(());
     }
 
+    let authenticator = match build_authenticator(&args) {
+        Ok(authenticator) => authenticator,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+
+    let access_policy = match &args.acl_file {
+        Some(acl_file) => match AccessPolicy::load(acl_file, &args.service_name) {
+            Ok(access_policy) => access_policy,
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        },
+        None => AccessPolicy::unrestricted(),
+    };
+
+    let audit_log = match AuditLog::open(&args.audit_file) {
+        Ok(audit_log) => web::Data::new(audit_log),
+        Err(e) => {
+            eprintln!("Failed to open audit file {}: {}", args.audit_file, e);
+            std::process::exit(1);
+        }
+    };
+
     // Initialize configuration
     let config = AppConfig {
-        username: args.username,
-        password: args.password,
-        services_dir: args.services_dir,
+        layout,
+        sv_timeout: args.sv_timeout,
+        status_timeout: args.status_timeout,
+        log_timeout: args.log_timeout,
+        status_concurrency: args.status_concurrency.into(),
+        stale_after: args.stale_after,
+        flap_window: args.flap_window,
+        flap_threshold: args.flap_threshold.into(),
+        flap_stop_after: args.flap_stop_after.map(usize::from),
     };
 
     Builder::new()
@@ -144,21 +283,46 @@ async fn main() -> std::io::Result<()> {
         .filter_level(args.log_level.parse().expect("Invalid log level"))
         .init();
 
+    info!("Recording service actions to {}", audit_log.path().display());
+
     let tera = load_embedded_templates().expect("Failed to load templates");
 
+    let state_cache = Arc::new(StateCache::new(config.clone(), audit_log.clone().into_inner()));
+    actix_web::rt::spawn(state_cache.clone().run());
+    let state_cache = web::Data::from(state_cache);
+
     HttpServer::new(move || {
-        let _auth = HttpAuthentication::basic(basic_auth_validator);
+        let auth = HttpAuthentication::basic(basic_auth_validator);
 
         App::new()
             .app_data(web::Data::new(config.clone()))
             .app_data(web::Data::new(tera.clone()))
-            //.wrap(auth) // Always wrap, validator handles bypass if no credentials
+            .app_data(web::Data::new(authenticator.clone()))
+            .app_data(web::Data::new(access_policy.clone()))
+            .app_data(audit_log.clone())
+            .app_data(state_cache.clone())
+            .app_data(web::PathConfig::default().error_handler(presentation::web_api::path_error))
+            .app_data(web::QueryConfig::default().error_handler(presentation::web_api::query_error))
+            .app_data(web::JsonConfig::default().error_handler(presentation::web_api::json_error))
+            .wrap(Condition::new(authenticator.is_enabled(), auth))
             .route("/", web::get().to(presentation::web_ui::render_service_list))
+            .route("/audit", web::get().to(presentation::web_ui::render_audit_log))
+            .route("/logs", web::get().to(presentation::web_ui::render_log_timeline))
+            .route("/services/{name}", web::get().to(presentation::web_ui::render_service_detail))
             .route("/services/{name}/log", web::get().to(presentation::web_ui::render_service_log))
+            .route("/services/{name}/log/config", web::get().to(presentation::web_ui::render_log_config))
             .route("/favicon.ico", web::get().to(favicon))
+            .route("/api/audit", web::get().to(presentation::web_api::render_audit_log))
+            .route("/api/events", web::get().to(presentation::web_api::stream_events))
+            .route("/api/logs", web::get().to(presentation::web_api::render_log_timeline))
             .route("/api/services", web::get().to(presentation::web_api::render_service_list))
             .route("/api/services/{name}", web::get().to(presentation::web_api::render_service_info))
             .route("/api/services/{name}/log", web::get().to(presentation::web_api::render_service_log))
+            .route("/api/services/{name}/tree", web::get().to(presentation::web_api::render_service_tree))
+            .route("/api/services/{name}/log/stream", web::get().to(presentation::web_api::stream_service_log))
+            .route("/api/services/{name}/log/download", web::get().to(presentation::web_api::download_service_log))
+            .route("/api/services/{name}/log/config", web::get().to(presentation::web_api::render_log_config))
+            .route("/api/services/{name}/log/config", web::put().to(presentation::web_api::update_log_config))
             .route("/api/services/{name}/{action}", web::post().to(presentation::web_api::manage_service))
     })
     .bind(&args.bind)?
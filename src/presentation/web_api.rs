@@ -1,49 +1,556 @@
-use actix_web::{web, HttpResponse};
+use std::io::Seek;
+use std::time::Duration;
+use actix_web::{web, HttpRequest, HttpResponse};
 use actix_web::Responder;
+use actix_web::error::{JsonPayloadError, PathError, QueryPayloadError};
+use actix_web::http::header::{ContentDisposition, DispositionParam, DispositionType};
+use actix_web::web::Bytes;
+use futures_util::{stream, StreamExt};
+use glob::Pattern;
+use log::warn;
 use serde::Deserialize;
 use serde_json::json;
+use tokio::io::AsyncReadExt;
 
 use crate::config::app_config::AppConfig;
+use crate::config::layout::RunitLayout;
 use crate::domain::service;
-use crate::application::manage_service;
-use crate::domain::service_logs;
+use crate::application::access_control::AccessPolicy;
+use crate::application::audit::{AuditEntry, AuditFilter, AuditLog};
+use crate::application::auth::AuthenticatedUser;
+use crate::application::blocking::run_blocking;
+use crate::application::manage_service::{self, ActionOutput, ServiceAction};
+use crate::domain::log_download::{self, DownloadFormat};
+use crate::domain::log_line::{self, LogLine};
+use crate::domain::log_search::{self, LogSearch};
+use crate::domain::log_timeline;
+use crate::domain::service_name::ServiceName;
+use crate::domain::service_logs::{self, LogCursor, PageAnchor, TimeRange};
+use crate::domain::service_tree;
+use crate::domain::svlogd_config::SvlogdConfig;
 use crate::application::service_info::ServiceInfo;
+use crate::application::state_cache::StateCache;
+use crate::application::state_events::{self, LiveEvent, Replay, ServiceEvent};
+use crate::error::AppError;
+
+/// Action name recorded in the audit log for log config edits.
+const LOG_CONFIG_AUDIT_ACTION: &str = "log-config";
 
 #[derive(Debug, Deserialize)]
 pub struct LogQuery {
     lines: Option<usize>,
+    /// Seconds since the UNIX epoch or a UTC `YYYY-MM-DDTHH:MM:SS` timestamp.
+    since: Option<String>,
+    until: Option<String>,
+    /// Substring (or regular expression with `regex=true`) to search for.
+    grep: Option<String>,
+    #[serde(default)]
+    regex: bool,
+    #[serde(default)]
+    invert: bool,
+    #[serde(default)]
+    ignore_case: bool,
+    /// Lines shown before and after each match when searching.
+    #[serde(default)]
+    context_before: usize,
+    #[serde(default)]
+    context_after: usize,
+    /// Cursors from a previous response, to page through the log; searches don't take them.
+    before: Option<String>,
+    after: Option<String>,
+    /// Maximum number of matches, newest first.
+    limit: Option<usize>,
 }
 
-pub async fn render_service_info(path: web::Path<String>) -> impl Responder {
-    let service_name = path.into_inner();
-    match ServiceInfo::get_status(&service_name) {
-        Ok(service_info) => HttpResponse::Ok().json(service_info.as_json()),
-        Err(_) => HttpResponse::NotFound().body(format!("Service {} not found", service_name)),
+impl LogQuery {
+    fn cursor(name: &str, value: &Option<String>) -> Result<Option<LogCursor>, AppError> {
+        value
+            .as_deref()
+            .map(|value| LogCursor::decode(value).ok_or_else(|| AppError::InvalidQuery(format!("{} is not a valid log cursor", name))))
+            .transpose()
+    }
+
+    fn page_anchor(&self) -> Result<PageAnchor, AppError> {
+        match (Self::cursor("before", &self.before)?, Self::cursor("after", &self.after)?) {
+            (Some(_), Some(_)) => Err(AppError::InvalidQuery("before and after are mutually exclusive".to_string())),
+            (Some(cursor), None) => Ok(PageAnchor::Before(cursor)),
+            (None, Some(cursor)) => Ok(PageAnchor::After(cursor)),
+            (None, None) => Ok(PageAnchor::Tail),
+        }
     }
 }
 
-pub async fn render_service_list(config: web::Data<AppConfig>) -> impl Responder {
-    let services_dir = &config.services_dir;
-    let service_list = service::fetch_service_list(services_dir);
-    let json_response = json!(service_list.iter().map(|s| s.as_json()).collect::<Vec<_>>());
-    HttpResponse::Ok().json(json_response)
+/// Parses the `since`/`until` bounds shared by the log queries.
+fn time_range(since: &Option<String>, until: &Option<String>) -> Result<TimeRange, AppError> {
+    let parse = |name: &str, value: &Option<String>| {
+        value
+            .as_deref()
+            .map(|value| {
+                log_line::parse_time_bound(value)
+                    .ok_or_else(|| AppError::InvalidQuery(format!("{} must be a UNIX timestamp or YYYY-MM-DDTHH:MM:SS", name)))
+            })
+            .transpose()
+    };
+
+    Ok(TimeRange {
+        since: parse("since", since)?,
+        until: parse("until", until)?,
+    })
 }
 
-pub async fn render_service_log(path: web::Path<String>, query: web::Query<LogQuery>) -> impl Responder {
-    let service_name = path.into_inner();
-    let service_info = ServiceInfo::get_status(&service_name).unwrap();
+#[derive(Debug, Deserialize)]
+pub struct TimelineQuery {
+    /// Comma-separated service names or glob patterns such as `app-*`.
+    services: String,
+    lines: Option<usize>,
+    since: Option<String>,
+    until: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DownloadQuery {
+    /// `tar.gz` (default), `zip` or `log`.
+    format: Option<String>,
+    since: Option<String>,
+    until: Option<String>,
+}
+
+/// Expands the names and glob patterns of a timeline query into service names.
+fn resolve_services(layout: &RunitLayout, spec: &str) -> Result<Vec<ServiceName>, AppError> {
+    let mut names: Vec<ServiceName> = Vec::new();
+    let mut known = None;
+
+    for item in spec.split(',').map(str::trim).filter(|item| !item.is_empty()) {
+        if !item.contains(['*', '?', '[']) {
+            let name = ServiceName::parse(item)?;
+            if !names.contains(&name) {
+                names.push(name);
+            }
+            continue;
+        }
+
+        let pattern = Pattern::new(item).map_err(|e| AppError::InvalidQuery(format!("invalid pattern {}: {}", item, e)))?;
+        let known = match &mut known {
+            Some(known) => known,
+            None => known.insert(service::service_names(layout)?),
+        };
+        for name in known.iter().filter(|name| pattern.matches(name)) {
+            if !names.contains(name) {
+                names.push(name.clone());
+            }
+        }
+    }
+
+    if names.is_empty() {
+        return Err(AppError::InvalidQuery("no services matched".to_string()));
+    }
+    Ok(names)
+}
+
+/// Reports unusable `{name}` segments as JSON errors; a path only fails to extract
+/// when its service name is rejected.
+pub fn path_error(err: PathError, _req: &HttpRequest) -> actix_web::Error {
+    match err {
+        PathError::Deserialize(e) => AppError::InvalidServiceName(e.to_string()).into(),
+        _ => err.into(),
+    }
+}
+
+/// Reports query strings that don't match the handler's parameters as JSON errors.
+pub fn query_error(err: QueryPayloadError, _req: &HttpRequest) -> actix_web::Error {
+    AppError::InvalidQuery(err.to_string()).into()
+}
+
+/// Reports request bodies that aren't the expected JSON as JSON errors.
+pub fn json_error(err: JsonPayloadError, _req: &HttpRequest) -> actix_web::Error {
+    AppError::InvalidBody(err.to_string()).into()
+}
+
+/// Service JSON extended with the actions the requesting user may perform.
+fn service_json(service_info: &ServiceInfo, policy: &AccessPolicy, user: Option<&str>) -> serde_json::Value {
+    let mut json = service_info.as_json();
+    json["allowed_actions"] = json!(policy.allowed_actions(user, &service_info.name));
+    json
+}
+
+pub async fn render_service_info(
+    state_cache: web::Data<StateCache>,
+    policy: web::Data<AccessPolicy>,
+    user: Option<web::ReqData<AuthenticatedUser>>,
+    path: web::Path<ServiceName>,
+) -> Result<HttpResponse, AppError> {
+    let service_info = state_cache.get(path.into_inner()).await?;
+    let user = user.map(|user| user.into_inner().0);
+    Ok(HttpResponse::Ok().json(service_json(&service_info, &policy, user.as_deref())))
+}
+
+pub async fn render_service_list(
+    state_cache: web::Data<StateCache>,
+    policy: web::Data<AccessPolicy>,
+    user: Option<web::ReqData<AuthenticatedUser>>,
+) -> impl Responder {
+    let (service_list, sequence) = state_cache.list();
+    let user = user.map(|user| user.into_inner().0);
+    let json_response = json!(service_list.iter().map(|s| service_json(s, &policy, user.as_deref())).collect::<Vec<_>>());
+    HttpResponse::Ok()
+        .insert_header((LAST_EVENT_ID_HEADER, sequence.to_string()))
+        .json(json_response)
+}
+
+pub async fn render_service_tree(config: web::Data<AppConfig>, path: web::Path<ServiceName>) -> Result<HttpResponse, AppError> {
+    let service_info = ServiceInfo::fetch_status(&config, path.into_inner()).await?;
+    let tree = run_blocking("reading process tree", config.status_timeout(), move || {
+        Ok(service_tree::service_tree(&service_info.registration.directory, service_info.pid))
+    })
+    .await?;
+    Ok(HttpResponse::Ok().json(json!({ "tree": tree })))
+}
+
+pub async fn render_service_log(config: web::Data<AppConfig>, path: web::Path<ServiceName>, query: web::Query<LogQuery>) -> Result<HttpResponse, AppError> {
+    let service_info = ServiceInfo::fetch_status(&config, path.into_inner()).await?;
+    let range = time_range(&query.since, &query.until)?;
+
+    let anchor = query.page_anchor()?;
+
+    if let Some(grep) = &query.grep {
+        if !matches!(anchor, PageAnchor::Tail) {
+            return Err(AppError::InvalidQuery(
+                "before and after cursors don't apply to searches, narrow them with since and until".to_string(),
+            ));
+        }
+        let search = LogSearch::new(
+            grep,
+            query.regex,
+            query.ignore_case,
+            query.invert,
+            query.context_before,
+            query.context_after,
+            query.limit.unwrap_or(100),
+        )?;
+        let logs = run_blocking("searching logs", config.log_timeout(), move || {
+            log_search::search_service_logs(&service_info, &search, range)
+        })
+        .await?;
+        return Ok(HttpResponse::Ok().json(json!({ "logs": logs })));
+    }
+
     let lines = query.lines.unwrap_or(50);
+    let page = run_blocking("reading logs", config.log_timeout(), move || {
+        service_logs::service_log_page(&service_info, lines, range, anchor)
+    })
+    .await?;
+    Ok(HttpResponse::Ok().json(page))
+}
+
+/// Interleaves the logs of several services by timestamp. Services whose logs
+/// can't be read are reported under `errors` instead of failing the request.
+pub async fn render_log_timeline(config: web::Data<AppConfig>, query: web::Query<TimelineQuery>) -> Result<HttpResponse, AppError> {
+    let range = time_range(&query.since, &query.until)?;
+    let lines = query.lines.unwrap_or(200);
+    let query = query.into_inner();
+    let layout = config.layout.clone();
+
+    let timeline = run_blocking("merging logs", config.log_timeout(), move || {
+        let services = resolve_services(&layout, &query.services)?;
+        let timeline = log_timeline::service_timeline(&services, lines, |service_name| {
+            ServiceInfo::get_status(&layout, service_name)
+                .and_then(|service_info| service_logs::service_log_lines(&service_info, range))
+        });
+        Ok(json!({ "services": services, "logs": timeline.logs, "errors": timeline.errors }))
+    })
+    .await?;
+    Ok(HttpResponse::Ok().json(timeline))
+}
+
+/// The svlogd config of a service, with problems found while parsing it.
+pub async fn render_log_config(
+    config: web::Data<AppConfig>,
+    policy: web::Data<AccessPolicy>,
+    user: Option<web::ReqData<AuthenticatedUser>>,
+    path: web::Path<ServiceName>,
+) -> Result<HttpResponse, AppError> {
+    let service_name = path.into_inner();
+    let service_info = ServiceInfo::fetch_status(&config, service_name.clone()).await?;
+    let log_dir = service_logs::svlogd_directory(&service_info)?.to_path_buf();
+    let (svlogd_config, problems) = {
+        let log_dir = log_dir.clone();
+        run_blocking("reading log config", config.status_timeout(), move || {
+            SvlogdConfig::read(&log_dir)
+                .map_err(|e| AppError::io(format!("Failed to read {}/config", log_dir.display()), e))
+        })
+        .await?
+    };
+
+    let user = user.map(|user| user.into_inner().0);
+    Ok(HttpResponse::Ok().json(json!({
+        "directory": log_dir,
+        "config": svlogd_config,
+        "problems": problems,
+        "editable": service_logs::editable_svlogd_directory(&service_info).is_ok()
+            && policy.may_edit_log_config(user.as_deref(), &service_name),
+    })))
+}
+
+pub async fn update_log_config(
+    req: HttpRequest,
+    config: web::Data<AppConfig>,
+    policy: web::Data<AccessPolicy>,
+    audit_log: web::Data<AuditLog>,
+    user: Option<web::ReqData<AuthenticatedUser>>,
+    path: web::Path<ServiceName>,
+    body: web::Json<SvlogdConfig>,
+) -> Result<HttpResponse, AppError> {
+    let service_name = path.into_inner();
+    let service_info = ServiceInfo::fetch_status(&config, service_name.clone()).await?;
+
+    let user = user.map(|user| user.into_inner().0);
+    let result = if policy.may_edit_log_config(user.as_deref(), &service_name) {
+        manage_service::update_log_config(&service_info, body.into_inner(), config.sv_timeout).await
+    } else {
+        Err(AppError::Forbidden(format!(
+            "{} may not edit the log config of service {}",
+            user.as_deref().unwrap_or("anonymous"),
+            service_name
+        )))
+    };
+
+    let client_ip = req.peer_addr().map(|addr| addr.ip().to_string());
+    let entry = AuditEntry::new(user, client_ip, &service_name, LOG_CONFIG_AUDIT_ACTION, &result);
+    audit_log.into_inner().record_async(entry).await;
+
+    Ok(HttpResponse::Ok().body(result?.message))
+}
+
+/// Size of the chunks a prepared download is streamed in.
+const DOWNLOAD_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Packs the logs of a service into a temporary file off the async runtime, then streams it.
+pub async fn download_service_log(config: web::Data<AppConfig>, path: web::Path<ServiceName>, query: web::Query<DownloadQuery>) -> Result<HttpResponse, AppError> {
+    let service_name = path.into_inner();
+    let service_info = ServiceInfo::fetch_status(&config, service_name.clone()).await?;
+    let range = time_range(&query.since, &query.until)?;
+    let format = match query.format.as_deref() {
+        None => DownloadFormat::TarGz,
+        Some(value) => DownloadFormat::from_str(value)
+            .ok_or_else(|| AppError::InvalidQuery(format!("unknown download format {}", value)))?,
+    };
+
+    let archive = run_blocking("packing logs", config.log_timeout(), move || {
+        let mut archive = tempfile::tempfile().map_err(|e| AppError::io("Failed to create temporary file", e))?;
+        log_download::write_log_download(&service_info, range, format, &mut archive)?;
+        archive.rewind().map_err(|e| AppError::io("Failed to rewind temporary file", e))?;
+        Ok(archive)
+    })
+    .await?;
+
+    let chunks = stream::unfold(tokio::fs::File::from_std(archive), |mut archive| async move {
+        let mut buffer = vec![0; DOWNLOAD_CHUNK_SIZE];
+        match archive.read(&mut buffer).await {
+            Ok(0) => None,
+            Ok(read) => {
+                buffer.truncate(read);
+                Some((Ok::<_, std::io::Error>(Bytes::from(buffer)), archive))
+            }
+            Err(e) => Some((Err(e), archive)),
+        }
+    });
+
+    let file_name = format!("{}-logs.{}", service_name, format.extension());
+    Ok(HttpResponse::Ok()
+        .content_type(format.content_type())
+        .insert_header(ContentDisposition {
+            disposition: DispositionType::Attachment,
+            parameters: vec![DispositionParam::Filename(file_name)],
+        })
+        .streaming(chunks))
+}
+
+/// How often the followed log file is checked for new lines.
+const LOG_STREAM_POLL_INTERVAL: Duration = Duration::from_millis(500);
+/// Number of idle polls after which a keep-alive comment is sent.
+const LOG_STREAM_KEEPALIVE_POLLS: u32 = 30;
+
+#[derive(Debug, Deserialize)]
+pub struct LogStreamQuery {
+    /// Cursor from a previous log response; lines after it are streamed first.
+    after: Option<String>,
+}
+
+/// The `Last-Event-ID` a reconnecting `EventSource` sends, the id of the last event it got.
+fn last_event_id(req: &HttpRequest) -> Option<&str> {
+    req.headers().get("Last-Event-ID").and_then(|value| value.to_str().ok())
+}
+
+/// Server-sent log lines, each with its cursor as event id. A reconnecting `EventSource`
+/// resumes after its `Last-Event-ID`, other clients after the `after` cursor.
+pub async fn stream_service_log(
+    req: HttpRequest,
+    config: web::Data<AppConfig>,
+    path: web::Path<ServiceName>,
+    query: web::Query<LogStreamQuery>,
+) -> Result<HttpResponse, AppError> {
+    let after = match last_event_id(&req) {
+        Some(id) => Some(LogCursor::decode(id).ok_or_else(|| AppError::InvalidQuery(format!("invalid Last-Event-ID {}", id)))?),
+        None => LogQuery::cursor("after", &query.after)?,
+    };
+    let service_info = ServiceInfo::fetch_status(&config, path.into_inner()).await?;
+    let tail = run_blocking("opening log", config.log_timeout(), move || service_logs::service_log_tail(&service_info, after)).await?;
+    let poll_timeout = config.log_timeout();
+
+    let events = stream::unfold((tail, 0u32), move |(mut tail, mut idle_polls)| async move {
+        loop {
+            tokio::time::sleep(LOG_STREAM_POLL_INTERVAL).await;
+
+            // The tail is handed to the blocking pool and back on every poll
+            let polled = run_blocking("following log", poll_timeout, move || {
+                let lines = tail.read_new_lines().map_err(|e| AppError::io("Failed to follow log file", e));
+                Ok((tail, lines))
+            })
+            .await;
+            let lines = match polled {
+                Ok((polled_tail, Ok(lines))) => {
+                    tail = polled_tail;
+                    lines
+                }
+                Ok((_, Err(e))) | Err(e) => {
+                    warn!("{}", e);
+                    return None;
+                }
+            };
 
-    match service_logs::service_logs(service_info, lines) {
-        Ok(logs) => HttpResponse::Ok().json(json!({ "logs": logs })),
-        Err(_) => HttpResponse::NotFound().json(json!({ "error": format!("Could not read logs for service {}", service_name) })),
+            if !lines.is_empty() {
+                let body: String = lines
+                    .iter()
+                    .map(|(line, cursor)| format!("id: {}\ndata: {}\n\n", cursor.encode(), json!(LogLine::parse(line))))
+                    .collect();
+                return Some((Ok::<_, actix_web::Error>(Bytes::from(body)), (tail, 0)));
+            }
+
+            idle_polls += 1;
+            if idle_polls >= LOG_STREAM_KEEPALIVE_POLLS {
+                return Some((Ok(Bytes::from_static(b": keep-alive\n\n")), (tail, 0)));
+            }
+        }
+    });
+
+    Ok(HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .insert_header(("Cache-Control", "no-cache"))
+        .streaming(events))
+}
+
+/// Sequence number of the latest state event, sent with the service list so clients can
+/// follow `/api/events` from it.
+const LAST_EVENT_ID_HEADER: &str = "X-Last-Event-Id";
+/// Time without events after which a keep-alive comment is sent.
+const EVENT_STREAM_KEEPALIVE: Duration = Duration::from_secs(15);
+
+#[derive(Debug, Deserialize)]
+pub struct EventQuery {
+    /// Replay the events after this sequence number before following new ones.
+    after: Option<u64>,
+}
+
+fn sse_event(event: &ServiceEvent) -> Bytes {
+    Bytes::from(format!("id: {}\ndata: {}\n\n", event.sequence, json!(event)))
+}
+
+/// Tells the client that events were missed and the full state must be reloaded.
+const RESYNC_EVENT: &[u8] = b"data: {\"type\":\"resync\"}\n\n";
+
+/// Server-sent service state events. A reconnecting `EventSource` resumes after its
+/// `Last-Event-ID`, other clients after the `after` sequence number.
+pub async fn stream_events(
+    req: HttpRequest,
+    state_cache: web::Data<StateCache>,
+    query: web::Query<EventQuery>,
+) -> Result<HttpResponse, AppError> {
+    let last_event_id = last_event_id(&req)
+        .map(|value| value.parse().map_err(|_| AppError::InvalidQuery(format!("invalid Last-Event-ID {}", value))))
+        .transpose()?;
+    let (replay, receiver) = state_cache.events().subscribe(last_event_id.or(query.after));
+
+    let replayed = match replay {
+        Replay::Events(events) => events.iter().map(sse_event).collect(),
+        Replay::Resync => vec![Bytes::from_static(RESYNC_EVENT)],
+    };
+    let live = stream::unfold(receiver, |mut receiver| async move {
+        let body = match tokio::time::timeout(EVENT_STREAM_KEEPALIVE, state_events::next_live(&mut receiver)).await {
+            Ok(Some(LiveEvent::Event(event))) => sse_event(&event),
+            Ok(Some(LiveEvent::Resync)) => Bytes::from_static(RESYNC_EVENT),
+            Ok(None) => return None,
+            Err(_) => Bytes::from_static(b": keep-alive\n\n"),
+        };
+        Some((body, receiver))
+    });
+    let events = stream::iter(replayed).chain(live).map(Ok::<_, actix_web::Error>);
+
+    Ok(HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .insert_header(("Cache-Control", "no-cache"))
+        .streaming(events))
+}
+
+async fn authorize_and_perform(
+    config: &AppConfig,
+    policy: &AccessPolicy,
+    user: Option<&str>,
+    service_name: &ServiceName,
+    action_name: &str,
+) -> Result<ActionOutput, AppError> {
+    let action = ServiceAction::from_str(action_name)
+        .ok_or_else(|| AppError::InvalidAction(action_name.to_string()))?;
+
+    if !policy.allows(user, service_name, &action) {
+        return Err(AppError::Forbidden(format!(
+            "{} may not {} service {}",
+            user.unwrap_or("anonymous"),
+            action.as_str(),
+            service_name
+        )));
     }
+
+    manage_service::perform_service_action(&config.layout, service_name, action, config.sv_timeout).await
 }
 
-pub async fn manage_service(path: web::Path<(String, String)>) -> impl Responder {
-    let (service_name, action) = path.into_inner();
-    match manage_service::perform_service_action(&service_name, &action) {
-        Ok(message) => HttpResponse::Ok().body(message),
-        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+pub async fn manage_service(
+    req: HttpRequest,
+    config: web::Data<AppConfig>,
+    policy: web::Data<AccessPolicy>,
+    audit_log: web::Data<AuditLog>,
+    state_cache: web::Data<StateCache>,
+    user: Option<web::ReqData<AuthenticatedUser>>,
+    path: web::Path<(ServiceName, String)>,
+) -> Result<HttpResponse, AppError> {
+    let (service_name, action_name) = path.into_inner();
+    ServiceInfo::fetch_status(&config, service_name.clone()).await?;
+
+    let user = user.map(|user| user.into_inner().0);
+    let result = authorize_and_perform(&config, &policy, user.as_deref(), &service_name, &action_name).await;
+    // inotify reports the change too, but not on every filesystem
+    if let Err(e) = state_cache.refresh(service_name.clone()).await {
+        warn!("Failed to refresh state of {}: {}", service_name, e);
     }
-}
\ No newline at end of file
+
+    let client_ip = req.peer_addr().map(|addr| addr.ip().to_string());
+    let entry = AuditEntry::new(user, client_ip, &service_name, &action_name, &result);
+    audit_log.into_inner().record_async(entry).await;
+
+    Ok(HttpResponse::Ok().body(result?.message))
+}
+
+pub async fn render_audit_log(
+    config: web::Data<AppConfig>,
+    audit_log: web::Data<AuditLog>,
+    query: web::Query<AuditFilter>,
+) -> Result<HttpResponse, AppError> {
+    let audit_log = audit_log.into_inner();
+    let query = query.into_inner();
+    let entries = run_blocking("reading audit log", config.log_timeout(), move || {
+        audit_log
+            .query(&query)
+            .map_err(|e| AppError::io("Failed to read audit log", e))
+    })
+    .await?;
+    Ok(HttpResponse::Ok().json(entries))
+}
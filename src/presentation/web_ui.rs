@@ -2,6 +2,9 @@ use actix_web::{web, HttpResponse, Responder};
 use tera::{Context, Tera};
 
 use crate::application::service_info::ServiceInfo;
+use crate::config::app_config::AppConfig;
+use crate::domain::service_name::ServiceName;
+use crate::error::AppError;
 
 pub async fn render_service_list(tera: web::Data<Tera>) -> impl Responder {
     let context = Context::new();
@@ -17,12 +20,40 @@ pub async fn render_service_list(tera: web::Data<Tera>) -> impl Responder {
     }
 }
 
-pub async fn render_service_log(path: web::Path<String>, tera: web::Data<Tera>) -> impl Responder {
-    let service_info = ServiceInfo::get_status(&path.into_inner()).unwrap();
+pub async fn render_audit_log(tera: web::Data<Tera>) -> impl Responder {
+    let context = Context::new();
+
+    match tera.render("web/audit.html", &context) {
+        Ok(rendered) => HttpResponse::Ok()
+            .content_type("text/html")
+            .body(rendered),
+        Err(_err) => {
+            HttpResponse::InternalServerError()
+                .body("Internal Server Error")
+        }
+    }
+}
+
+pub async fn render_log_timeline(tera: web::Data<Tera>) -> impl Responder {
+    let context = Context::new();
+
+    match tera.render("web/timeline.html", &context) {
+        Ok(rendered) => HttpResponse::Ok()
+            .content_type("text/html")
+            .body(rendered),
+        Err(_err) => {
+            HttpResponse::InternalServerError()
+                .body("Internal Server Error")
+        }
+    }
+}
+
+pub async fn render_service_detail(config: web::Data<AppConfig>, path: web::Path<ServiceName>, tera: web::Data<Tera>) -> Result<HttpResponse, AppError> {
+    let service_info = ServiceInfo::fetch_status(&config, path.into_inner()).await?;
     let mut context = Context::new();
     context.insert("service", &service_info);
 
-    match tera.render("web/logs.html", &context) {
+    Ok(match tera.render("web/service.html", &context) {
         Ok(rendered) => HttpResponse::Ok()
             .content_type("text/html")
             .body(rendered),
@@ -30,5 +61,37 @@ pub async fn render_service_log(path: web::Path<String>, tera: web::Data<Tera>)
             HttpResponse::InternalServerError()
                 .body("Internal Server Error")
         }
-    }
+    })
+}
+
+pub async fn render_service_log(config: web::Data<AppConfig>, path: web::Path<ServiceName>, tera: web::Data<Tera>) -> Result<HttpResponse, AppError> {
+    let service_info = ServiceInfo::fetch_status(&config, path.into_inner()).await?;
+    let mut context = Context::new();
+    context.insert("service", &service_info);
+
+    Ok(match tera.render("web/logs.html", &context) {
+        Ok(rendered) => HttpResponse::Ok()
+            .content_type("text/html")
+            .body(rendered),
+        Err(_err) => {
+            HttpResponse::InternalServerError()
+                .body("Internal Server Error")
+        }
+    })
+}
+
+pub async fn render_log_config(config: web::Data<AppConfig>, path: web::Path<ServiceName>, tera: web::Data<Tera>) -> Result<HttpResponse, AppError> {
+    let service_info = ServiceInfo::fetch_status(&config, path.into_inner()).await?;
+    let mut context = Context::new();
+    context.insert("service", &service_info);
+
+    Ok(match tera.render("web/log_config.html", &context) {
+        Ok(rendered) => HttpResponse::Ok()
+            .content_type("text/html")
+            .body(rendered),
+        Err(_err) => {
+            HttpResponse::InternalServerError()
+                .body("Internal Server Error")
+        }
+    })
 }